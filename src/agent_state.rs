@@ -0,0 +1,231 @@
+//! Agent state snapshot registry
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use parking_lot::RwLock;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::warn;
+
+use crate::error::HookError;
+
+/// A subsystem whose state is captured in checkpoints
+pub trait AgentState: Send + Sync {
+    /// Capture the current state
+    fn snapshot(&self) -> Result<serde_json::Value, HookError>;
+
+    /// Replace the current state with a snapshot
+    fn restore(&self, state: &serde_json::Value) -> Result<(), HookError>;
+}
+
+/// Adapter turning a typed snapshot/restore closure pair into an [`AgentState`]
+struct FnState<T, S, R> {
+    snapshot: S,
+    restore: R,
+    _state: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T, S, R> AgentState for FnState<T, S, R>
+where
+    T: Serialize + DeserializeOwned,
+    S: Fn() -> T + Send + Sync,
+    R: Fn(T) -> Result<(), HookError> + Send + Sync,
+{
+    fn snapshot(&self) -> Result<serde_json::Value, HookError> {
+        Ok(serde_json::to_value((self.snapshot)())?)
+    }
+
+    fn restore(&self, state: &serde_json::Value) -> Result<(), HookError> {
+        (self.restore)(T::deserialize(state)?)
+    }
+}
+
+/// Outcome of snapshotting agent states
+#[derive(Debug, Clone, Default)]
+pub struct AgentStateSnapshot {
+    /// Serialized state of each participant that snapshotted successfully
+    pub states: HashMap<String, serde_json::Value>,
+    /// Participants that failed to snapshot, with the error
+    pub failed: HashMap<String, String>,
+}
+
+/// Outcome of restoring agent states
+#[derive(Debug, Clone, Default)]
+pub struct AgentStateRestore {
+    /// Participants that restored their state
+    pub restored: Vec<String>,
+    /// Participants that failed, with the error
+    pub failed: Vec<(String, String)>,
+    /// Registered participants with no saved state, left as they are
+    pub missing: Vec<String>,
+}
+
+/// Named participants whose state is snapshotted with each checkpoint
+///
+/// A failing participant is logged and reported, never stopping the others.
+#[derive(Default)]
+pub struct AgentStateRegistry {
+    participants: RwLock<BTreeMap<String, Arc<dyn AgentState>>>,
+}
+
+impl AgentStateRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a participant, replacing any with the same name
+    pub fn register(&self, name: impl Into<String>, state: Arc<dyn AgentState>) {
+        self.participants.write().insert(name.into(), state);
+    }
+
+    /// Register a snapshot/restore pair over any serializable state
+    pub fn register_fns<T, S, R>(&self, name: impl Into<String>, snapshot: S, restore: R)
+    where
+        T: Serialize + DeserializeOwned + 'static,
+        S: Fn() -> T + Send + Sync + 'static,
+        R: Fn(T) -> Result<(), HookError> + Send + Sync + 'static,
+    {
+        self.register(name, Arc::new(FnState {
+            snapshot,
+            restore,
+            _state: std::marker::PhantomData,
+        }));
+    }
+
+    /// Remove a participant
+    pub fn unregister(&self, name: &str) -> bool {
+        self.participants.write().remove(name).is_some()
+    }
+
+    /// Names of registered participants, sorted
+    pub fn names(&self) -> Vec<String> {
+        self.participants.read().keys().cloned().collect()
+    }
+
+    /// Snapshot every participant, collecting those that fail
+    pub fn snapshot_all(&self) -> AgentStateSnapshot {
+        let mut snapshot = AgentStateSnapshot::default();
+        for (name, participant) in self.participants() {
+            match participant.snapshot() {
+                Ok(state) => {
+                    snapshot.states.insert(name, state);
+                }
+                Err(e) => {
+                    warn!(participant = %name, error = %e, "Failed to snapshot agent state");
+                    snapshot.failed.insert(name, e.to_string());
+                }
+            }
+        }
+        snapshot
+    }
+
+    /// Restore every registered participant that has a saved state.
+    /// Participants without one are reported as missing; states of
+    /// participants that are no longer registered are ignored.
+    pub fn restore_all(&self, states: &HashMap<String, serde_json::Value>) -> AgentStateRestore {
+        let mut outcome = AgentStateRestore::default();
+        for (name, participant) in self.participants() {
+            let Some(state) = states.get(&name) else {
+                outcome.missing.push(name);
+                continue;
+            };
+            match participant.restore(state) {
+                Ok(()) => outcome.restored.push(name),
+                Err(e) => {
+                    warn!(participant = %name, error = %e, "Failed to restore agent state");
+                    outcome.failed.push((name, e.to_string()));
+                }
+            }
+        }
+        outcome
+    }
+
+    /// Clone the participant list so hooks run without holding the lock
+    fn participants(&self) -> Vec<(String, Arc<dyn AgentState>)> {
+        self.participants.read()
+            .iter()
+            .map(|(name, state)| (name.clone(), state.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use serde_json::json;
+
+    struct Broken;
+
+    impl AgentState for Broken {
+        fn snapshot(&self) -> Result<serde_json::Value, HookError> {
+            Err("no snapshot".into())
+        }
+
+        fn restore(&self, _state: &serde_json::Value) -> Result<(), HookError> {
+            Err("no restore".into())
+        }
+    }
+
+    #[test]
+    fn test_register_fns_round_trip() {
+        let registry = AgentStateRegistry::new();
+        let plan = Arc::new(Mutex::new(vec!["read code".to_string()]));
+
+        let (snap, restore) = (plan.clone(), plan.clone());
+        registry.register_fns(
+            "planner",
+            move || snap.lock().clone(),
+            move |steps: Vec<String>| {
+                *restore.lock() = steps;
+                Ok(())
+            },
+        );
+
+        let states = registry.snapshot_all().states;
+        assert_eq!(states["planner"], json!(["read code"]));
+
+        plan.lock().push("rewrite everything".to_string());
+        let outcome = registry.restore_all(&states);
+
+        assert_eq!(outcome.restored, vec!["planner".to_string()]);
+        assert_eq!(*plan.lock(), vec!["read code".to_string()]);
+    }
+
+    #[test]
+    fn test_failures_do_not_stop_others() {
+        let registry = AgentStateRegistry::new();
+        registry.register("broken", Arc::new(Broken));
+        registry.register_fns("counter", || 3u32, |_: u32| Ok(()));
+
+        let snapshot = registry.snapshot_all();
+        assert_eq!(snapshot.states.len(), 1);
+        assert_eq!(snapshot.failed["broken"], "no snapshot");
+
+        let outcome = registry.restore_all(&snapshot.states);
+        assert_eq!(outcome.restored, vec!["counter".to_string()]);
+        assert_eq!(outcome.missing, vec!["broken".to_string()]);
+
+        let mut saved = snapshot.states.clone();
+        saved.insert("broken".to_string(), json!(null));
+        saved.insert("gone".to_string(), json!(1));
+        let outcome = registry.restore_all(&saved);
+
+        assert_eq!(outcome.restored, vec!["counter".to_string()]);
+        assert_eq!(outcome.failed, vec![("broken".to_string(), "no restore".to_string())]);
+        assert!(outcome.missing.is_empty());
+    }
+
+    #[test]
+    fn test_restore_type_mismatch_is_an_error() {
+        let registry = AgentStateRegistry::new();
+        registry.register_fns("counter", || 3u32, |_: u32| Ok(()));
+
+        let outcome = registry.restore_all(&HashMap::from([("counter".to_string(), json!("three"))]));
+
+        assert_eq!(outcome.failed.len(), 1);
+        assert!(registry.unregister("counter"));
+        assert!(registry.names().is_empty());
+    }
+}
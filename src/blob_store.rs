@@ -0,0 +1,231 @@
+//! Content-addressed blob storage for file contents
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tracing::debug;
+
+use crate::error::CheckpointError;
+use crate::storage::write_atomic;
+
+/// SHA-256 digest of a blob's content, hex encoded
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BlobHash(String);
+
+impl BlobHash {
+    /// Hash some content
+    pub fn of(content: &[u8]) -> Self {
+        Self(format!("{:x}", Sha256::digest(content)))
+    }
+
+    /// Get the hex digest
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for BlobHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Reference to a stored blob
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobRef {
+    /// Content digest
+    pub hash: BlobHash,
+    /// Content length in bytes
+    pub size: u64,
+}
+
+impl BlobRef {
+    /// Build a reference for some content without storing it
+    pub fn for_content(content: &[u8]) -> Self {
+        Self {
+            hash: BlobHash::of(content),
+            size: content.len() as u64,
+        }
+    }
+}
+
+/// Deduplicating blob store with reference counting
+///
+/// Blobs are written once per distinct content under `base_dir`, fanned
+/// out by the first two hex digits of their hash. Reference counts are
+/// kept in memory and rebuilt from the checkpoints when storage is opened.
+pub struct BlobStore {
+    /// Base directory for blobs
+    base_dir: PathBuf,
+    /// Number of checkpoints referencing each blob
+    refs: Mutex<HashMap<BlobHash, usize>>,
+}
+
+impl BlobStore {
+    /// Create a new blob store
+    pub fn new(base_dir: PathBuf) -> Self {
+        Self {
+            base_dir,
+            refs: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Store content, writing it only if no identical blob exists yet
+    pub async fn put(&self, content: &[u8]) -> Result<BlobRef, CheckpointError> {
+        let blob = BlobRef::for_content(content);
+        let path = self.blob_path(&blob.hash);
+        
+        if path.exists() {
+            return Ok(blob);
+        }
+        
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await
+                .map_err(|e| CheckpointError::StorageError(format!(
+                    "Failed to create blob directory: {}", e
+                )))?;
+        }
+        
+        // Written atomically so a crash never leaves a truncated blob
+        write_atomic(&path, content).await
+            .map_err(|e| CheckpointError::StorageError(format!(
+                "Failed to write blob: {}", e
+            )))?;
+        
+        debug!(hash = %blob.hash, size = blob.size, "Stored blob");
+        Ok(blob)
+    }
+
+    /// Read a blob's content
+    pub async fn get(&self, hash: &BlobHash) -> Result<Vec<u8>, CheckpointError> {
+        fs::read(self.blob_path(hash)).await
+            .map_err(|e| CheckpointError::StorageError(format!(
+                "Failed to read blob {}: {}", hash, e
+            )))
+    }
+
+    /// Read a blob's content as UTF-8 text
+    pub async fn get_string(&self, hash: &BlobHash) -> Result<String, CheckpointError> {
+        String::from_utf8(self.get(hash).await?)
+            .map_err(|e| CheckpointError::StorageError(format!(
+                "Blob {} is not valid UTF-8: {}", hash, e
+            )))
+    }
+
+    /// Check if a blob exists in storage
+    pub fn exists(&self, hash: &BlobHash) -> bool {
+        self.blob_path(hash).exists()
+    }
+
+    /// Add a reference to a blob
+    pub fn retain(&self, hash: &BlobHash) {
+        *self.refs.lock().entry(hash.clone()).or_insert(0) += 1;
+    }
+
+    /// Drop a reference to a blob, deleting it once nothing references it.
+    ///
+    /// Returns whether the blob was deleted.
+    pub async fn release(&self, hash: &BlobHash) -> Result<bool, CheckpointError> {
+        let unreferenced = {
+            let mut refs = self.refs.lock();
+            match refs.get_mut(hash) {
+                Some(count) if *count > 1 => {
+                    *count -= 1;
+                    false
+                }
+                Some(_) => {
+                    refs.remove(hash);
+                    true
+                }
+                None => false,
+            }
+        };
+        
+        if !unreferenced {
+            return Ok(false);
+        }
+        
+        let path = self.blob_path(hash);
+        if path.exists() {
+            fs::remove_file(&path).await
+                .map_err(|e| CheckpointError::StorageError(format!(
+                    "Failed to delete blob {}: {}", hash, e
+                )))?;
+        }
+        
+        debug!(hash = %hash, "Reclaimed unreferenced blob");
+        Ok(true)
+    }
+
+    /// Get the number of references to a blob
+    pub fn ref_count(&self, hash: &BlobHash) -> usize {
+        self.refs.lock().get(hash).copied().unwrap_or(0)
+    }
+
+    // Private helper
+    fn blob_path(&self, hash: &BlobHash) -> PathBuf {
+        let (prefix, rest) = hash.as_str().split_at(2);
+        self.base_dir.join(prefix).join(rest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_blob_hash_is_content_addressed() {
+        assert_eq!(BlobHash::of(b"hello"), BlobHash::of(b"hello"));
+        assert_ne!(BlobHash::of(b"hello"), BlobHash::of(b"world"));
+        assert_eq!(BlobHash::of(b"hello").as_str().len(), 64);
+    }
+
+    #[tokio::test]
+    async fn test_put_get_roundtrip() {
+        let dir = tempdir().unwrap();
+        let store = BlobStore::new(dir.path().to_path_buf());
+        
+        let blob = store.put(b"fn main() {}").await.unwrap();
+        
+        assert_eq!(blob.size, 12);
+        assert!(store.exists(&blob.hash));
+        assert_eq!(store.get_string(&blob.hash).await.unwrap(), "fn main() {}");
+    }
+
+    #[tokio::test]
+    async fn test_put_deduplicates() {
+        let dir = tempdir().unwrap();
+        let store = BlobStore::new(dir.path().to_path_buf());
+        
+        let a = store.put(b"same").await.unwrap();
+        let b = store.put(b"same").await.unwrap();
+        
+        assert_eq!(a, b);
+        let fanout = dir.path().join(&a.hash.as_str()[..2]);
+        assert_eq!(std::fs::read_dir(fanout).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_release_reclaims_unreferenced() {
+        let dir = tempdir().unwrap();
+        let store = BlobStore::new(dir.path().to_path_buf());
+        
+        let blob = store.put(b"shared").await.unwrap();
+        store.retain(&blob.hash);
+        store.retain(&blob.hash);
+        assert_eq!(store.ref_count(&blob.hash), 2);
+        
+        assert!(!store.release(&blob.hash).await.unwrap());
+        assert!(store.exists(&blob.hash));
+        
+        assert!(store.release(&blob.hash).await.unwrap());
+        assert!(!store.exists(&blob.hash));
+        assert_eq!(store.ref_count(&blob.hash), 0);
+    }
+}
@@ -1,11 +1,13 @@
 //! Checkpoint data structures
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 
 use warhorn::{CheckpointId, CheckpointMeta, TaskId};
+use crate::blob_store::BlobRef;
+use crate::file_tracker::ChangeSource;
 
 /// A single checkpoint
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +22,9 @@ pub struct Checkpoint {
     pub task_id: Option<TaskId>,
     /// Turn number (for auto-checkpoints)
     pub turn_number: Option<u32>,
+    /// Checkpoint this one was taken on top of (None for a root)
+    #[serde(default)]
+    pub parent: Option<CheckpointId>,
     /// Checkpoint data
     pub data: CheckpointData,
 }
@@ -52,7 +57,7 @@ impl Checkpoint {
     pub fn estimated_size(&self) -> u64 {
         // Simple estimate based on file content sizes
         self.data.file_states.values()
-            .map(|s| s.len() as u64)
+            .map(|blob| blob.size)
             .sum()
     }
 }
@@ -60,16 +65,82 @@ impl Checkpoint {
 /// Data stored in a checkpoint
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct CheckpointData {
-    /// File contents at checkpoint time
-    pub file_states: HashMap<PathBuf, String>,
+    /// File contents at checkpoint time, stored in the blob store
+    pub file_states: HashMap<PathBuf, BlobRef>,
+    /// Files known not to exist at checkpoint time
+    #[serde(default)]
+    pub deleted_files: HashSet<PathBuf>,
+    /// Files created by tracked changes (absent before the session)
+    #[serde(default)]
+    pub created_files: HashSet<PathBuf>,
+    /// Content files had before their first tracked change
+    #[serde(default)]
+    pub original_states: HashMap<PathBuf, BlobRef>,
+    /// Changes recorded since the previous checkpoint, oldest first
+    #[serde(default)]
+    pub changes: Vec<ChangeRecord>,
     /// Conversation history snapshot
     pub conversation_snapshot: Option<ConversationSnapshot>,
+    /// Conversation relative to the parent checkpoint's. Only set in
+    /// storage, in place of `conversation_snapshot`; expanded on load.
+    #[serde(default)]
+    pub conversation_delta: Option<ConversationDelta>,
     /// Agent state snapshots
     pub agent_states: HashMap<String, serde_json::Value>,
+    /// Agent state participants that failed to snapshot, with the error
+    #[serde(default)]
+    pub agent_state_errors: HashMap<String, String>,
 }
 
-/// Snapshot of conversation history
+impl CheckpointData {
+    /// Every blob reference held by this checkpoint, including repeats
+    pub fn blob_refs(&self) -> impl Iterator<Item = &BlobRef> {
+        self.file_states.values()
+            .chain(self.original_states.values())
+            .chain(self.changes.iter().flat_map(|c| c.old_content.iter().chain(&c.new_content)))
+    }
+
+    /// File states as they were before the recorded changes, rebuilt from
+    /// the journal for when the parent checkpoint is gone. Files whose
+    /// earlier content is unknown are left out.
+    pub fn before_changes(&self) -> CheckpointData {
+        let mut file_states = self.file_states.clone();
+        let mut deleted_files = self.deleted_files.clone();
+        for change in self.changes.iter().rev() {
+            deleted_files.remove(&change.path);
+            match &change.old_content {
+                Some(blob) => file_states.insert(change.path.clone(), blob.clone()),
+                None => file_states.remove(&change.path),
+            };
+        }
+
+        CheckpointData {
+            file_states,
+            deleted_files,
+            ..Default::default()
+        }
+    }
+}
+
+/// A file change recorded between a checkpoint's parent and the checkpoint
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeRecord {
+    /// File path
+    pub path: PathBuf,
+    /// Content before the change (None if the file did not exist or was unknown)
+    pub old_content: Option<BlobRef>,
+    /// Content after the change (None if the file was deleted)
+    pub new_content: Option<BlobRef>,
+    /// When the change was recorded
+    #[serde(default)]
+    pub timestamp: DateTime<Utc>,
+    /// What made the change
+    #[serde(default)]
+    pub source: ChangeSource,
+}
+
+/// Snapshot of conversation history
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ConversationSnapshot {
     /// Messages in the conversation
     pub messages: Vec<ConversationMessage>,
@@ -77,17 +148,220 @@ pub struct ConversationSnapshot {
     pub token_count: u64,
 }
 
+impl ConversationSnapshot {
+    /// Describe this snapshot as an edit of `parent`'s, or `None` if the
+    /// two share no leading messages
+    pub fn delta_from(&self, parent: &ConversationSnapshot) -> Option<ConversationDelta> {
+        let kept = self.messages.iter()
+            .zip(&parent.messages)
+            .take_while(|(ours, theirs)| ours == theirs)
+            .count();
+        if kept == 0 {
+            return None;
+        }
+
+        Some(ConversationDelta {
+            kept,
+            appended: self.messages[kept..].to_vec(),
+            token_count: self.token_count,
+        })
+    }
+}
+
+/// A conversation snapshot stored as an edit of its parent's
+///
+/// Conversations mostly grow by appending, so this is usually just the new
+/// messages. When messages were removed or rewritten, `kept` is less than
+/// the parent's message count and marks where the parent was truncated.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConversationDelta {
+    /// Leading messages kept from the parent's snapshot
+    pub kept: usize,
+    /// Messages following the kept ones
+    pub appended: Vec<ConversationMessage>,
+    /// Total token count
+    pub token_count: u64,
+}
+
+impl ConversationDelta {
+    /// Rebuild the full snapshot from the parent's, or `None` if the parent
+    /// has fewer messages than were kept
+    pub fn apply(&self, parent: &ConversationSnapshot) -> Option<ConversationSnapshot> {
+        let mut messages = parent.messages.get(..self.kept)?.to_vec();
+        messages.extend(self.appended.iter().cloned());
+        Some(ConversationSnapshot {
+            messages,
+            token_count: self.token_count,
+        })
+    }
+}
+
 /// A message in the conversation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Everything beyond role, content and timestamp is optional, so snapshots
+/// saved before these fields existed still load.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ConversationMessage {
-    /// Role (user, assistant, system, tool)
-    pub role: String,
-    /// Message content
+    /// Provider-assigned message id
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Who sent the message
+    pub role: MessageRole,
+    /// Message text
     pub content: String,
+    /// Tools the assistant asked to run
+    #[serde(default)]
+    pub tool_calls: Vec<ToolCall>,
+    /// Result of a tool call, for tool messages
+    #[serde(default)]
+    pub tool_result: Option<ToolResult>,
+    /// Tokens used by this message, if known
+    #[serde(default)]
+    pub token_count: Option<u64>,
     /// Timestamp
     pub timestamp: DateTime<Utc>,
 }
 
+impl ConversationMessage {
+    /// Create a plain text message timestamped now
+    pub fn new(role: MessageRole, content: impl Into<String>) -> Self {
+        Self {
+            id: None,
+            role,
+            content: content.into(),
+            tool_calls: Vec::new(),
+            tool_result: None,
+            token_count: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Create a tool message carrying the result of a call
+    pub fn tool_result(call_id: impl Into<String>, output: impl Into<String>, is_error: bool) -> Self {
+        let output = output.into();
+        Self {
+            tool_result: Some(ToolResult {
+                call_id: call_id.into(),
+                output: output.clone(),
+                is_error,
+            }),
+            ..Self::new(MessageRole::Tool, output)
+        }
+    }
+
+    /// Set the message id
+    pub fn with_id(mut self, id: impl Into<String>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Set the token count
+    pub fn with_token_count(mut self, tokens: u64) -> Self {
+        self.token_count = Some(tokens);
+        self
+    }
+
+    /// Add a tool call
+    pub fn with_tool_call(mut self, call: ToolCall) -> Self {
+        self.tool_calls.push(call);
+        self
+    }
+}
+
+/// Sender of a conversation message
+///
+/// Serialized as a lowercase string. Roles hutch does not know are kept
+/// as [`MessageRole::Other`] rather than rejected.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum MessageRole {
+    /// System prompt
+    System,
+    /// Human user
+    User,
+    /// The model
+    Assistant,
+    /// Tool output
+    Tool,
+    /// Any other role, as given
+    Other(String),
+}
+
+impl MessageRole {
+    /// Role name as serialized
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::System => "system",
+            Self::User => "user",
+            Self::Assistant => "assistant",
+            Self::Tool => "tool",
+            Self::Other(role) => role,
+        }
+    }
+}
+
+impl From<String> for MessageRole {
+    fn from(role: String) -> Self {
+        match role.as_str() {
+            "system" => Self::System,
+            "user" => Self::User,
+            "assistant" => Self::Assistant,
+            "tool" => Self::Tool,
+            _ => Self::Other(role),
+        }
+    }
+}
+
+impl From<MessageRole> for String {
+    fn from(role: MessageRole) -> Self {
+        match role {
+            MessageRole::Other(role) => role,
+            known => known.as_str().to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for MessageRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A tool invocation requested by the assistant
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Call id, matched by [`ToolResult::call_id`]
+    pub id: String,
+    /// Tool name
+    pub name: String,
+    /// Tool arguments
+    #[serde(default)]
+    pub input: serde_json::Value,
+}
+
+impl ToolCall {
+    /// Create a tool call
+    pub fn new(id: impl Into<String>, name: impl Into<String>, input: serde_json::Value) -> Self {
+        Self {
+            id: id.into(),
+            name: name.into(),
+            input,
+        }
+    }
+}
+
+/// Output of a tool call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolResult {
+    /// Id of the call this answers
+    pub call_id: String,
+    /// Tool output
+    pub output: String,
+    /// Whether the tool failed
+    #[serde(default)]
+    pub is_error: bool,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -102,6 +376,7 @@ mod tests {
             timestamp: Utc::now(),
             task_id: None,
             turn_number: None,
+            parent: None,
             data: CheckpointData::default(),
         };
         
@@ -116,6 +391,7 @@ mod tests {
             timestamp: Utc::now(),
             task_id: None,
             turn_number: Some(5),
+            parent: None,
             data: CheckpointData::default(),
         };
         
@@ -130,6 +406,7 @@ mod tests {
             timestamp: Utc::now(),
             task_id: None,
             turn_number: None,
+            parent: None,
             data: CheckpointData::default(),
         };
         
@@ -144,6 +421,7 @@ mod tests {
             timestamp: Utc::now(),
             task_id: None,
             turn_number: Some(5),
+            parent: None,
             data: CheckpointData::default(),
         };
         
@@ -161,6 +439,7 @@ mod tests {
             timestamp: Utc::now(),
             task_id: None,
             turn_number: None,
+            parent: None,
             data: CheckpointData::default(),
         };
         
@@ -170,8 +449,8 @@ mod tests {
     #[test]
     fn test_checkpoint_size_with_files() {
         let mut data = CheckpointData::default();
-        data.file_states.insert(PathBuf::from("/a.txt"), "hello".to_string());
-        data.file_states.insert(PathBuf::from("/b.txt"), "world".to_string());
+        data.file_states.insert(PathBuf::from("/a.txt"), BlobRef::for_content(b"hello"));
+        data.file_states.insert(PathBuf::from("/b.txt"), BlobRef::for_content(b"world"));
         
         let checkpoint = Checkpoint {
             id: CheckpointId::new(),
@@ -179,6 +458,7 @@ mod tests {
             timestamp: Utc::now(),
             task_id: None,
             turn_number: None,
+            parent: None,
             data,
         };
         
@@ -189,7 +469,7 @@ mod tests {
     fn test_checkpoint_size_large_files() {
         let mut data = CheckpointData::default();
         let large_content = "x".repeat(10_000);
-        data.file_states.insert(PathBuf::from("/large.txt"), large_content);
+        data.file_states.insert(PathBuf::from("/large.txt"), BlobRef::for_content(large_content.as_bytes()));
         
         let checkpoint = Checkpoint {
             id: CheckpointId::new(),
@@ -197,6 +477,7 @@ mod tests {
             timestamp: Utc::now(),
             task_id: None,
             turn_number: None,
+            parent: None,
             data,
         };
         
@@ -213,6 +494,7 @@ mod tests {
             timestamp: Utc::now(),
             task_id: Some(TaskId::new()),
             turn_number: Some(3),
+            parent: None,
             data: CheckpointData::default(),
         };
         
@@ -229,7 +511,7 @@ mod tests {
     #[test]
     fn test_checkpoint_to_meta_with_size() {
         let mut data = CheckpointData::default();
-        data.file_states.insert(PathBuf::from("/test.txt"), "content".to_string());
+        data.file_states.insert(PathBuf::from("/test.txt"), BlobRef::for_content(b"content"));
         
         let checkpoint = Checkpoint {
             id: CheckpointId::new(),
@@ -237,6 +519,7 @@ mod tests {
             timestamp: Utc::now(),
             task_id: None,
             turn_number: Some(1),
+            parent: None,
             data,
         };
         
@@ -252,6 +535,8 @@ mod tests {
         let data: CheckpointData = Default::default();
         
         assert!(data.file_states.is_empty());
+        assert!(data.deleted_files.is_empty());
+        assert!(data.created_files.is_empty());
         assert!(data.conversation_snapshot.is_none());
         assert!(data.agent_states.is_empty());
     }
@@ -259,32 +544,76 @@ mod tests {
     #[test]
     fn test_checkpoint_data_with_files() {
         let mut data = CheckpointData::default();
-        data.file_states.insert(PathBuf::from("/a.rs"), "fn main() {}".to_string());
-        data.file_states.insert(PathBuf::from("/b.rs"), "mod test;".to_string());
+        data.file_states.insert(PathBuf::from("/a.rs"), BlobRef::for_content(b"fn main() {}"));
+        data.file_states.insert(PathBuf::from("/b.rs"), BlobRef::for_content(b"mod test;"));
         
         assert_eq!(data.file_states.len(), 2);
     }
 
+    #[test]
+    fn test_blob_refs_include_journal() {
+        let mut data = CheckpointData::default();
+        data.file_states.insert(PathBuf::from("/a.rs"), BlobRef::for_content(b"v2"));
+        data.original_states.insert(PathBuf::from("/a.rs"), BlobRef::for_content(b"v0"));
+        data.changes.push(ChangeRecord {
+            path: PathBuf::from("/a.rs"),
+            old_content: Some(BlobRef::for_content(b"v1")),
+            new_content: Some(BlobRef::for_content(b"v2")),
+            timestamp: Utc::now(),
+            source: ChangeSource::tool("edit_file"),
+        });
+        
+        assert_eq!(data.blob_refs().count(), 4);
+    }
+
+    #[test]
+    fn test_before_changes_rewinds_journal() {
+        let change = |path: &str, old: Option<&[u8]>, new: Option<&[u8]>| ChangeRecord {
+            path: PathBuf::from(path),
+            old_content: old.map(BlobRef::for_content),
+            new_content: new.map(BlobRef::for_content),
+            timestamp: Utc::now(),
+            source: ChangeSource::default(),
+        };
+        let mut data = CheckpointData::default();
+        data.file_states.insert(PathBuf::from("/a.rs"), BlobRef::for_content(b"v2"));
+        data.file_states.insert(PathBuf::from("/new.rs"), BlobRef::for_content(b"new"));
+        data.file_states.insert(PathBuf::from("/same.rs"), BlobRef::for_content(b"same"));
+        data.deleted_files.insert(PathBuf::from("/gone.rs"));
+        data.changes = vec![
+            change("/a.rs", Some(b"v0"), Some(b"v1")),
+            change("/a.rs", Some(b"v1"), Some(b"v2")),
+            change("/new.rs", None, Some(b"new")),
+            change("/gone.rs", Some(b"old"), None),
+        ];
+        
+        let before = data.before_changes();
+        
+        assert_eq!(before.file_states[&PathBuf::from("/a.rs")], BlobRef::for_content(b"v0"));
+        assert_eq!(before.file_states[&PathBuf::from("/gone.rs")], BlobRef::for_content(b"old"));
+        assert_eq!(before.file_states[&PathBuf::from("/same.rs")], BlobRef::for_content(b"same"));
+        assert!(!before.file_states.contains_key(&PathBuf::from("/new.rs")));
+        assert!(before.deleted_files.is_empty());
+    }
+
     #[test]
     fn test_checkpoint_data_with_conversation() {
         let data = CheckpointData {
             file_states: HashMap::new(),
+            deleted_files: HashSet::new(),
+            created_files: HashSet::new(),
+            original_states: HashMap::new(),
+            changes: Vec::new(),
             conversation_snapshot: Some(ConversationSnapshot {
                 messages: vec![
-                    ConversationMessage {
-                        role: "user".to_string(),
-                        content: "Hello".to_string(),
-                        timestamp: Utc::now(),
-                    },
-                    ConversationMessage {
-                        role: "assistant".to_string(),
-                        content: "Hi there!".to_string(),
-                        timestamp: Utc::now(),
-                    },
+                    ConversationMessage::new(MessageRole::User, "Hello"),
+                    ConversationMessage::new(MessageRole::Assistant, "Hi there!"),
                 ],
                 token_count: 100,
             }),
+            conversation_delta: None,
             agent_states: HashMap::new(),
+            agent_state_errors: HashMap::new(),
         };
         
         let snapshot = data.conversation_snapshot.unwrap();
@@ -309,11 +638,7 @@ mod tests {
     fn test_conversation_snapshot() {
         let snapshot = ConversationSnapshot {
             messages: vec![
-                ConversationMessage {
-                    role: "system".to_string(),
-                    content: "You are an assistant".to_string(),
-                    timestamp: Utc::now(),
-                },
+                ConversationMessage::new(MessageRole::System, "You are an assistant"),
             ],
             token_count: 50,
         };
@@ -322,6 +647,55 @@ mod tests {
         assert_eq!(snapshot.token_count, 50);
     }
 
+    #[test]
+    fn test_conversation_delta_append() {
+        let parent = ConversationSnapshot {
+            messages: vec![ConversationMessage::new(MessageRole::User, "Hello")],
+            token_count: 5,
+        };
+        let mut child = parent.clone();
+        child.messages.push(ConversationMessage::new(MessageRole::Assistant, "Hi!"));
+        child.token_count = 9;
+        
+        let delta = child.delta_from(&parent).unwrap();
+        assert_eq!(delta.kept, 1);
+        assert_eq!(delta.appended, child.messages[1..]);
+        assert_eq!(delta.apply(&parent), Some(child));
+    }
+
+    #[test]
+    fn test_conversation_delta_truncation() {
+        let parent = ConversationSnapshot {
+            messages: vec![
+                ConversationMessage::new(MessageRole::User, "Hello"),
+                ConversationMessage::new(MessageRole::Assistant, "Off-topic rambling"),
+                ConversationMessage::new(MessageRole::User, "Stop"),
+            ],
+            token_count: 30,
+        };
+        let child = ConversationSnapshot {
+            messages: vec![
+                parent.messages[0].clone(),
+                ConversationMessage::new(MessageRole::Assistant, "Hi!"),
+            ],
+            token_count: 9,
+        };
+        
+        let delta = child.delta_from(&parent).unwrap();
+        assert_eq!(delta.kept, 1);
+        assert_eq!(delta.appended.len(), 1);
+        assert_eq!(delta.apply(&parent), Some(child.clone()));
+        
+        let unrelated = ConversationSnapshot {
+            messages: vec![ConversationMessage::new(MessageRole::System, "Fresh start")],
+            token_count: 2,
+        };
+        assert!(unrelated.delta_from(&parent).is_none());
+        
+        let empty = ConversationSnapshot { messages: Vec::new(), token_count: 0 };
+        assert!(delta.apply(&empty).is_none());
+    }
+
     // === ConversationMessage Tests ===
 
     #[test]
@@ -329,13 +703,44 @@ mod tests {
         let roles = vec!["user", "assistant", "system", "tool"];
         
         for role in roles {
-            let msg = ConversationMessage {
-                role: role.to_string(),
-                content: "test".to_string(),
-                timestamp: Utc::now(),
-            };
-            assert_eq!(msg.role, role);
+            let msg = ConversationMessage::new(MessageRole::from(role.to_string()), "test");
+            assert_eq!(msg.role.as_str(), role);
+            assert!(!matches!(msg.role, MessageRole::Other(_)));
         }
+        
+        assert_eq!(MessageRole::from("developer".to_string()), MessageRole::Other("developer".to_string()));
+    }
+
+    #[test]
+    fn test_conversation_message_tool_call_round_trip() {
+        let call = ConversationMessage::new(MessageRole::Assistant, "Let me look")
+            .with_id("msg-1")
+            .with_token_count(12)
+            .with_tool_call(ToolCall::new("call-1", "read_file", serde_json::json!({"path": "src/lib.rs"})));
+        let result = ConversationMessage::tool_result("call-1", "no such file", true);
+        
+        let json = serde_json::to_value([&call, &result]).unwrap();
+        assert_eq!(json[0]["role"], "assistant");
+        assert_eq!(json[0]["tool_calls"][0]["name"], "read_file");
+        
+        let parsed: Vec<ConversationMessage> = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, vec![call, result]);
+        assert_eq!(parsed[1].role, MessageRole::Tool);
+        assert!(parsed[1].tool_result.as_ref().unwrap().is_error);
+    }
+
+    #[test]
+    fn test_conversation_message_legacy_shape() {
+        let json = r#"{"role": "user", "content": "Hello", "timestamp": "2024-01-01T00:00:00Z"}"#;
+        let msg: ConversationMessage = serde_json::from_str(json).unwrap();
+        
+        assert_eq!(msg.role, MessageRole::User);
+        assert_eq!(msg.content, "Hello");
+        assert!(msg.id.is_none() && msg.tool_calls.is_empty() && msg.tool_result.is_none());
+        assert_eq!(msg.token_count, None);
+        
+        let custom: ConversationMessage = serde_json::from_str(&json.replace("user", "narrator")).unwrap();
+        assert_eq!(serde_json::to_value(&custom).unwrap()["role"], "narrator");
     }
 
     // === Serialization Tests ===
@@ -348,6 +753,7 @@ mod tests {
             timestamp: Utc::now(),
             task_id: None,
             turn_number: Some(1),
+            parent: None,
             data: CheckpointData::default(),
         };
         
@@ -362,7 +768,7 @@ mod tests {
     #[test]
     fn test_checkpoint_data_serialization() {
         let mut data = CheckpointData::default();
-        data.file_states.insert(PathBuf::from("/test.txt"), "content".to_string());
+        data.file_states.insert(PathBuf::from("/test.txt"), BlobRef::for_content(b"content"));
         
         let json = serde_json::to_string(&data).unwrap();
         let parsed: CheckpointData = serde_json::from_str(&json).unwrap();
@@ -374,11 +780,7 @@ mod tests {
     fn test_conversation_snapshot_serialization() {
         let snapshot = ConversationSnapshot {
             messages: vec![
-                ConversationMessage {
-                    role: "user".to_string(),
-                    content: "Hello".to_string(),
-                    timestamp: Utc::now(),
-                },
+                ConversationMessage::new(MessageRole::User, "Hello"),
             ],
             token_count: 10,
         };
@@ -389,4 +791,23 @@ mod tests {
         assert_eq!(parsed.messages.len(), 1);
         assert_eq!(parsed.token_count, 10);
     }
+
+    #[test]
+    fn test_checkpoint_deserialize_without_parent() {
+        let checkpoint = Checkpoint {
+            id: CheckpointId::new(),
+            name: None,
+            timestamp: Utc::now(),
+            task_id: None,
+            turn_number: Some(1),
+            parent: Some(CheckpointId::new()),
+            data: CheckpointData::default(),
+        };
+        
+        let mut json = serde_json::to_value(&checkpoint).unwrap();
+        json.as_object_mut().unwrap().remove("parent");
+        let parsed: Checkpoint = serde_json::from_value(json).unwrap();
+        
+        assert!(parsed.parent.is_none());
+    }
 }
@@ -0,0 +1,22 @@
+//! Conversation snapshots supplied by the host agent
+
+use crate::checkpoint::ConversationSnapshot;
+use crate::error::HookError;
+
+/// Supplies and restores the agent's conversation at checkpoint boundaries
+///
+/// Register an implementation with
+/// [`CheckpointManager::set_conversation_provider`](crate::CheckpointManager::set_conversation_provider).
+/// Every checkpoint then stores the snapshot returned by [`snapshot`], and
+/// restoring a checkpoint (including undo, redo and branch switches)
+/// hands its snapshot back to [`restore`] after the files are restored.
+///
+/// [`snapshot`]: ConversationProvider::snapshot
+/// [`restore`]: ConversationProvider::restore
+pub trait ConversationProvider: Send + Sync {
+    /// Capture the conversation as it is now
+    fn snapshot(&self) -> ConversationSnapshot;
+
+    /// Rewind the conversation to a snapshot taken by [`snapshot`](Self::snapshot)
+    fn restore(&self, snapshot: &ConversationSnapshot) -> Result<(), HookError>;
+}
@@ -0,0 +1,387 @@
+//! Line-level diffs between checkpoints
+
+use std::fmt::{self, Write};
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+
+use warhorn::CheckpointId;
+
+/// Lines of unchanged context around each hunk
+pub const DEFAULT_CONTEXT_LINES: usize = 3;
+
+/// Line-level diff between two checkpoints
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointDiff {
+    /// Checkpoint diffed from
+    pub from: CheckpointId,
+    /// Checkpoint diffed to
+    pub to: CheckpointId,
+    /// Changed files, sorted by path
+    pub files: Vec<FileLineDiff>,
+}
+
+impl CheckpointDiff {
+    /// Check if diff is empty
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Total inserted lines across all files
+    pub fn insertions(&self) -> usize {
+        self.files.iter().map(|f| f.insertions).sum()
+    }
+
+    /// Total deleted lines across all files
+    pub fn deletions(&self) -> usize {
+        self.files.iter().map(|f| f.deletions).sum()
+    }
+
+    /// Render as unified diff text
+    pub fn to_unified(&self) -> String {
+        self.files.iter().map(|f| f.to_unified()).collect()
+    }
+}
+
+/// How a file changed between two checkpoints
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FileChangeKind {
+    /// File only exists in the newer checkpoint
+    Added,
+    /// File exists in both with different content
+    Modified,
+    /// File only exists in the older checkpoint
+    Deleted,
+}
+
+/// Line-level diff of a single file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileLineDiff {
+    /// File path
+    pub path: PathBuf,
+    /// Kind of change
+    pub kind: FileChangeKind,
+    /// Whether either side is binary (no hunks are produced)
+    pub binary: bool,
+    /// Changed regions
+    pub hunks: Vec<Hunk>,
+    /// Number of inserted lines
+    pub insertions: usize,
+    /// Number of deleted lines
+    pub deletions: usize,
+}
+
+impl FileLineDiff {
+    /// Diff two versions of a file. `None` means the file does not exist
+    /// on that side; non-UTF-8 content produces a binary diff.
+    pub fn compute(
+        path: PathBuf,
+        old: Option<&[u8]>,
+        new: Option<&[u8]>,
+        context: usize,
+    ) -> Self {
+        let kind = match (old, new) {
+            (None, _) => FileChangeKind::Added,
+            (_, None) => FileChangeKind::Deleted,
+            _ => FileChangeKind::Modified,
+        };
+        let old_text = old.map(std::str::from_utf8).unwrap_or(Ok(""));
+        let new_text = new.map(std::str::from_utf8).unwrap_or(Ok(""));
+
+        let (Ok(old_text), Ok(new_text)) = (old_text, new_text) else {
+            return Self {
+                path,
+                kind,
+                binary: true,
+                hunks: Vec::new(),
+                insertions: 0,
+                deletions: 0,
+            };
+        };
+
+        let hunks = diff_lines(old_text, new_text, context);
+        let count = |kind: LineKind| hunks.iter()
+            .flat_map(|h| &h.lines)
+            .filter(|l| l.kind == kind)
+            .count();
+
+        Self {
+            path,
+            kind,
+            binary: false,
+            insertions: count(LineKind::Insert),
+            deletions: count(LineKind::Delete),
+            hunks,
+        }
+    }
+
+    /// Render as unified diff text
+    pub fn to_unified(&self) -> String {
+        let mut out = String::new();
+        let path = self.path.display();
+        let old_name = match self.kind {
+            FileChangeKind::Added => "/dev/null".to_string(),
+            _ => format!("a{}{}", separator(&self.path), path),
+        };
+        let new_name = match self.kind {
+            FileChangeKind::Deleted => "/dev/null".to_string(),
+            _ => format!("b{}{}", separator(&self.path), path),
+        };
+
+        if self.binary {
+            let _ = writeln!(out, "Binary files {} and {} differ", old_name, new_name);
+            return out;
+        }
+
+        let _ = writeln!(out, "--- {}", old_name);
+        let _ = writeln!(out, "+++ {}", new_name);
+        for hunk in &self.hunks {
+            let _ = write!(out, "{}", hunk);
+        }
+        out
+    }
+}
+
+/// A contiguous changed region with surrounding context
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hunk {
+    /// First line in the old file (1-based, 0 if the range is empty)
+    pub old_start: usize,
+    /// Number of old lines covered
+    pub old_lines: usize,
+    /// First line in the new file (1-based, 0 if the range is empty)
+    pub new_start: usize,
+    /// Number of new lines covered
+    pub new_lines: usize,
+    /// Lines in the hunk
+    pub lines: Vec<DiffLine>,
+}
+
+impl fmt::Display for Hunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "@@ -{} +{} @@",
+            format_range(self.old_start, self.old_lines),
+            format_range(self.new_start, self.new_lines),
+        )?;
+        for line in &self.lines {
+            let prefix = match line.kind {
+                LineKind::Context => ' ',
+                LineKind::Insert => '+',
+                LineKind::Delete => '-',
+            };
+            writeln!(f, "{}{}", prefix, line.text)?;
+            if line.missing_newline {
+                writeln!(f, "\\ No newline at end of file")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Kind of a diff line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LineKind {
+    /// Unchanged line
+    Context,
+    /// Line only in the new file
+    Insert,
+    /// Line only in the old file
+    Delete,
+}
+
+/// A single line in a hunk
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiffLine {
+    /// Kind of line
+    pub kind: LineKind,
+    /// Line text without its line terminator
+    pub text: String,
+    /// Line number in the old file (1-based)
+    pub old_line: Option<usize>,
+    /// Line number in the new file (1-based)
+    pub new_line: Option<usize>,
+    /// Whether this is the last line and lacks a trailing newline
+    pub missing_newline: bool,
+}
+
+/// Compute hunks between two texts
+pub fn diff_lines(old: &str, new: &str, context: usize) -> Vec<Hunk> {
+    let diff = TextDiff::from_lines(old, new);
+    let mut hunks = Vec::new();
+
+    for group in diff.grouped_ops(context) {
+        let (Some(first), Some(last)) = (group.first(), group.last()) else {
+            continue;
+        };
+        let old_range = first.old_range().start..last.old_range().end;
+        let new_range = first.new_range().start..last.new_range().end;
+
+        let lines = group.iter()
+            .flat_map(|op| diff.iter_changes(op))
+            .map(|change| {
+                let text = change.value();
+                DiffLine {
+                    kind: match change.tag() {
+                        ChangeTag::Equal => LineKind::Context,
+                        ChangeTag::Insert => LineKind::Insert,
+                        ChangeTag::Delete => LineKind::Delete,
+                    },
+                    text: text.strip_suffix('\n')
+                        .map(|t| t.strip_suffix('\r').unwrap_or(t))
+                        .unwrap_or(text)
+                        .to_string(),
+                    old_line: change.old_index().map(|i| i + 1),
+                    new_line: change.new_index().map(|i| i + 1),
+                    missing_newline: change.missing_newline(),
+                }
+            })
+            .collect();
+
+        hunks.push(Hunk {
+            old_start: hunk_start(old_range.start, old_range.len()),
+            old_lines: old_range.len(),
+            new_start: hunk_start(new_range.start, new_range.len()),
+            new_lines: new_range.len(),
+            lines,
+        });
+    }
+
+    hunks
+}
+
+fn hunk_start(start: usize, len: usize) -> usize {
+    // Empty ranges point at the line just before them
+    if len == 0 { start } else { start + 1 }
+}
+
+fn format_range(start: usize, len: usize) -> String {
+    if len == 1 {
+        start.to_string()
+    } else {
+        format!("{},{}", start, len)
+    }
+}
+
+fn separator(path: &std::path::Path) -> &'static str {
+    if path.has_root() { "" } else { "/" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines_single_change() {
+        let old = "a\nb\nc\n";
+        let new = "a\nB\nc\n";
+
+        let hunks = diff_lines(old, new, 3);
+
+        assert_eq!(hunks.len(), 1);
+        assert_eq!(hunks[0].old_start, 1);
+        assert_eq!(hunks[0].old_lines, 3);
+        assert_eq!(hunks[0].new_lines, 3);
+
+        let changed: Vec<_> = hunks[0].lines.iter()
+            .filter(|l| l.kind != LineKind::Context)
+            .map(|l| (l.kind, l.text.as_str(), l.old_line, l.new_line))
+            .collect();
+        assert_eq!(changed, vec![
+            (LineKind::Delete, "b", Some(2), None),
+            (LineKind::Insert, "B", None, Some(2)),
+        ]);
+    }
+
+    #[test]
+    fn test_diff_lines_separate_hunks() {
+        let line = |i: usize| match i {
+            2 => "two\n".to_string(),
+            19 => "nineteen\n".to_string(),
+            _ => format!("{}\n", i),
+        };
+        let old: String = (1..=20).map(|i| format!("{}\n", i)).collect();
+        let new: String = (1..=20).map(line).collect();
+
+        let hunks = diff_lines(&old, &new, 3);
+
+        assert_eq!(hunks.len(), 2);
+        assert_eq!(hunks[1].old_start, 16);
+    }
+
+    #[test]
+    fn test_compute_counts() {
+        let diff = FileLineDiff::compute(
+            PathBuf::from("/src/lib.rs"),
+            Some(b"one\ntwo\n"),
+            Some(b"one\n2\nthree\n"),
+            DEFAULT_CONTEXT_LINES,
+        );
+
+        assert_eq!(diff.kind, FileChangeKind::Modified);
+        assert_eq!(diff.insertions, 2);
+        assert_eq!(diff.deletions, 1);
+    }
+
+    #[test]
+    fn test_compute_added_and_deleted() {
+        let added = FileLineDiff::compute(PathBuf::from("/new.txt"), None, Some(b"x\ny\n"), 3);
+        assert_eq!(added.kind, FileChangeKind::Added);
+        assert_eq!(added.insertions, 2);
+        assert_eq!(added.hunks[0].old_start, 0);
+
+        let deleted = FileLineDiff::compute(PathBuf::from("/old.txt"), Some(b"x\n"), None, 3);
+        assert_eq!(deleted.kind, FileChangeKind::Deleted);
+        assert_eq!(deleted.deletions, 1);
+    }
+
+    #[test]
+    fn test_compute_binary() {
+        let diff = FileLineDiff::compute(
+            PathBuf::from("/img.png"),
+            Some(&[0xff, 0x00]),
+            Some(&[0xfe]),
+            3,
+        );
+
+        assert!(diff.binary);
+        assert!(diff.hunks.is_empty());
+        assert_eq!(diff.to_unified(), "Binary files a/img.png and b/img.png differ\n");
+    }
+
+    #[test]
+    fn test_to_unified() {
+        let diff = FileLineDiff::compute(
+            PathBuf::from("src/main.rs"),
+            Some(b"fn main() {\n    old();\n}\n"),
+            Some(b"fn main() {\n    new();\n}\n"),
+            3,
+        );
+
+        assert_eq!(diff.to_unified(), "\
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    old();
++    new();
+ }
+");
+    }
+
+    #[test]
+    fn test_to_unified_missing_newline() {
+        let diff = FileLineDiff::compute(PathBuf::from("f"), Some(b"a"), Some(b"b"), 3);
+
+        assert_eq!(diff.to_unified(), "\
+--- a/f
++++ b/f
+@@ -1 +1 @@
+-a
+\\ No newline at end of file
++b
+\\ No newline at end of file
+");
+    }
+}
@@ -1,7 +1,12 @@
 //! Checkpoint error types
 
+use std::path::PathBuf;
 use thiserror::Error;
-use warhorn::CheckpointId;
+use warhorn::{CheckpointId, TaskId};
+use crate::restore::RestoreFailure;
+
+/// Error returned by host-supplied snapshot and restore hooks
+pub type HookError = Box<dyn std::error::Error + Send + Sync>;
 
 /// Errors that can occur in checkpoint operations
 #[derive(Debug, Error)]
@@ -10,10 +15,42 @@ pub enum CheckpointError {
     #[error("Checkpoint not found: {0}")]
     NotFound(CheckpointId),
 
+    /// No checkpoint recorded for a task's turn
+    #[error("No checkpoint for turn {1} of task {0}")]
+    TurnNotFound(TaskId, u32),
+
+    /// Malformed path or glob pattern
+    #[error("Invalid path pattern: {0}")]
+    InvalidPattern(String),
+
+    /// Relative path pattern given without a workspace root to resolve it
+    #[error("Relative pattern {0} needs a workspace root")]
+    NoWorkspaceRoot(String),
+
+    /// Filesystem watcher error
+    #[error("Watch error: {0}")]
+    WatchError(String),
+
+    /// No conversation provider is registered
+    #[error("No conversation provider registered")]
+    NoConversationProvider,
+
+    /// Checkpoint was taken without a conversation snapshot
+    #[error("Checkpoint {0} has no conversation snapshot")]
+    NoConversationSnapshot(CheckpointId),
+
+    /// Conversation provider failed to restore a snapshot
+    #[error("Conversation restore failed: {0}")]
+    ConversationError(String),
+
     /// Nothing to undo
     #[error("Nothing to undo")]
     NothingToUndo,
 
+    /// Nothing to redo
+    #[error("Nothing to redo")]
+    NothingToRedo,
+
     /// Auto-checkpoint is disabled
     #[error("Auto-checkpoint is disabled")]
     AutoCheckpointDisabled,
@@ -26,6 +63,14 @@ pub enum CheckpointError {
     #[error("Restore error: {0}")]
     RestoreError(String),
 
+    /// Restore failed part-way; changes already applied were rolled back
+    #[error("Restore failed: {0}")]
+    RestoreFailed(RestoreFailure),
+
+    /// Files were modified outside of hutch since it last recorded them
+    #[error("Restore would overwrite {} externally modified file(s)", .0.len())]
+    Conflict(Vec<PathBuf>),
+
     /// IO error
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
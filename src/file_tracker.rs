@@ -1,12 +1,104 @@
 //! File change tracking
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Raw file content, text or binary
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct FileContent(Vec<u8>);
+
+impl FileContent {
+    /// Create content from raw bytes
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// Get the raw bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Take the raw bytes
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Get the content as text, if it is valid UTF-8
+    pub fn as_text(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+
+    /// Check if the content is valid UTF-8
+    pub fn is_text(&self) -> bool {
+        self.as_text().is_some()
+    }
+
+    /// Get content length in bytes
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Check if the content is empty
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for FileContent {
+    fn from(text: String) -> Self {
+        Self(text.into_bytes())
+    }
+}
+
+impl From<&str> for FileContent {
+    fn from(text: &str) -> Self {
+        Self(text.as_bytes().to_vec())
+    }
+}
+
+impl From<Vec<u8>> for FileContent {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<&[u8]> for FileContent {
+    fn from(bytes: &[u8]) -> Self {
+        Self(bytes.to_vec())
+    }
+}
+
+impl AsRef<[u8]> for FileContent {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for FileContent {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
+
+impl PartialEq<&str> for FileContent {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == other.as_bytes()
+    }
+}
 
 /// Tracks file changes for checkpoint/restore
 pub struct FileTracker {
     /// Current file states
-    states: HashMap<PathBuf, String>,
+    states: HashMap<PathBuf, FileContent>,
+    /// Files known to be deleted
+    deleted: HashSet<PathBuf>,
+    /// Files that did not exist until a tracked change created them
+    created: HashSet<PathBuf>,
+    /// Content files had before their first tracked change; files that
+    /// did not exist are in `created` instead
+    originals: HashMap<PathBuf, FileContent>,
     /// Pending changes since last checkpoint
     pending_changes: Vec<FileChange>,
 }
@@ -17,11 +109,38 @@ pub struct FileChange {
     /// File path
     pub path: PathBuf,
     /// Content before change (None if new file)
-    pub old_content: Option<String>,
-    /// Content after change
-    pub new_content: String,
-    /// Timestamp
-    pub timestamp: std::time::Instant,
+    pub old_content: Option<FileContent>,
+    /// Content after change (None if file was deleted)
+    pub new_content: Option<FileContent>,
+    /// When the change was recorded
+    pub timestamp: DateTime<Utc>,
+    /// What made the change
+    pub source: ChangeSource,
+}
+
+/// What made a change, for auditing the change journal
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeSource {
+    /// Tool that made the change
+    pub tool: Option<String>,
+    /// Why the change was made
+    pub reason: Option<String>,
+}
+
+impl ChangeSource {
+    /// Change made by a tool
+    pub fn tool(name: impl Into<String>) -> Self {
+        Self {
+            tool: Some(name.into()),
+            reason: None,
+        }
+    }
+
+    /// Add a reason
+    pub fn with_reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
 }
 
 impl FileTracker {
@@ -29,29 +148,146 @@ impl FileTracker {
     pub fn new() -> Self {
         Self {
             states: HashMap::new(),
+            deleted: HashSet::new(),
+            created: HashSet::new(),
+            originals: HashMap::new(),
             pending_changes: Vec::new(),
         }
     }
 
-    /// Record a file change
+    /// Record a text file change
     pub fn record_change(
         &mut self,
         path: PathBuf,
         old_content: Option<String>,
         new_content: String,
     ) {
+        self.record_content_change(path, old_content.map(FileContent::from), new_content.into());
+    }
+
+    /// Record a binary file change
+    pub fn record_binary_change(
+        &mut self,
+        path: PathBuf,
+        old_content: Option<Vec<u8>>,
+        new_content: Vec<u8>,
+    ) {
+        self.record_content_change(path, old_content.map(FileContent::from), new_content.into());
+    }
+
+    /// Record a file change with arbitrary content
+    pub fn record_content_change(
+        &mut self,
+        path: PathBuf,
+        old_content: Option<FileContent>,
+        new_content: FileContent,
+    ) {
+        self.record_content_change_from(path, old_content, new_content, ChangeSource::default());
+    }
+
+    /// Record a file change made by a known source
+    pub fn record_content_change_from(
+        &mut self,
+        path: PathBuf,
+        old_content: Option<FileContent>,
+        new_content: FileContent,
+        source: ChangeSource,
+    ) {
+        if !self.is_known(&path) {
+            match &old_content {
+                Some(original) => {
+                    self.originals.insert(path.clone(), original.clone());
+                }
+                None => {
+                    self.created.insert(path.clone());
+                }
+            }
+        }
+
         self.pending_changes.push(FileChange {
             path: path.clone(),
             old_content,
-            new_content: new_content.clone(),
-            timestamp: std::time::Instant::now(),
+            new_content: Some(new_content.clone()),
+            timestamp: Utc::now(),
+            source,
         });
         
+        self.deleted.remove(&path);
         self.states.insert(path, new_content);
     }
 
+    /// Record a file deletion
+    pub fn record_deletion(&mut self, path: PathBuf, old_content: Option<FileContent>) {
+        self.record_deletion_from(path, old_content, ChangeSource::default());
+    }
+
+    /// Record a file deletion made by a known source
+    pub fn record_deletion_from(
+        &mut self,
+        path: PathBuf,
+        old_content: Option<FileContent>,
+        source: ChangeSource,
+    ) {
+        let old_content = old_content.or_else(|| self.states.get(&path).cloned());
+        if let Some(original) = old_content.as_ref().filter(|_| !self.is_known(&path)) {
+            self.originals.insert(path.clone(), original.clone());
+        }
+
+        self.pending_changes.push(FileChange {
+            path: path.clone(),
+            old_content,
+            new_content: None,
+            timestamp: Utc::now(),
+            source,
+        });
+        
+        self.states.remove(&path);
+        self.deleted.insert(path);
+    }
+
+    /// Record the content a file had before any tracked change.
+    ///
+    /// Unlike a change, this is not pending and does not mark the file as
+    /// created. Files that are already known are left alone; returns
+    /// whether the file was added.
+    pub fn record_baseline(&mut self, path: PathBuf, content: FileContent) -> bool {
+        if self.is_known(&path) {
+            return false;
+        }
+
+        self.states.insert(path, content);
+        true
+    }
+
+    /// Record a file's content as observed on disk (`None` if it is gone).
+    ///
+    /// The old content is taken from the last known state. A file hutch
+    /// has never seen is only recorded if it was `created`, since the
+    /// content of an existing file from before the change is unknown and
+    /// it must not be mistaken for a new file (and removed on restore).
+    /// Returns whether anything was recorded.
+    pub fn record_observed(&mut self, path: PathBuf, content: Option<FileContent>, created: bool) -> bool {
+        if !created && !self.is_known(&path) {
+            return false;
+        }
+
+        let known = self.states.get(&path);
+        match content {
+            Some(content) if known != Some(&content) => {
+                let old_content = known.cloned();
+                self.record_content_change(path, old_content, content);
+                true
+            }
+            None if known.is_some() => {
+                self.record_deletion(path, None);
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Get current file states
-    pub fn current_states(&self) -> HashMap<PathBuf, String> {
+    pub fn current_states(&self) -> HashMap<PathBuf, FileContent> {
         self.states.clone()
     }
 
@@ -65,14 +301,81 @@ impl FileTracker {
         self.pending_changes.clear();
     }
 
+    /// Drop the oldest `count` pending changes, once a checkpoint has
+    /// stored them
+    pub fn discard_pending(&mut self, count: usize) {
+        let count = count.min(self.pending_changes.len());
+        self.pending_changes.drain(..count);
+    }
+
+    /// Get the content a file had before its first tracked change
+    pub fn original_state(&self, path: &PathBuf) -> Option<&FileContent> {
+        self.originals.get(path)
+    }
+
+    /// Get the pre-change content of every file that had one
+    pub fn original_states(&self) -> &HashMap<PathBuf, FileContent> {
+        &self.originals
+    }
+
+    /// Remember original contents, keeping any already known
+    pub fn add_originals(&mut self, originals: HashMap<PathBuf, FileContent>) {
+        for (path, content) in originals {
+            self.originals.entry(path).or_insert(content);
+        }
+    }
+
+    /// Get files known to be deleted
+    pub fn deleted_paths(&self) -> HashSet<PathBuf> {
+        self.deleted.clone()
+    }
+
+    /// Get files that were created by tracked changes
+    pub fn created_paths(&self) -> HashSet<PathBuf> {
+        self.created.clone()
+    }
+
+    /// Mark files as created by tracked changes
+    pub fn mark_created(&mut self, paths: impl IntoIterator<Item = PathBuf>) {
+        self.created.extend(paths);
+    }
+
     /// Reset to a specific state
-    pub fn reset_to(&mut self, states: &HashMap<PathBuf, String>) {
+    ///
+    /// Which files were created by tracked changes is history rather than
+    /// state, so it survives the reset.
+    pub fn reset_to(
+        &mut self,
+        states: &HashMap<PathBuf, FileContent>,
+        deleted: &HashSet<PathBuf>,
+    ) {
         self.states = states.clone();
+        self.deleted = deleted.clone();
         self.pending_changes.clear();
     }
 
+    /// Reset only the given paths, leaving every other file untouched.
+    /// Paths in neither `states` nor `deleted` become untracked.
+    pub fn reset_paths(
+        &mut self,
+        paths: &HashSet<PathBuf>,
+        states: &HashMap<PathBuf, FileContent>,
+        deleted: &HashSet<PathBuf>,
+    ) {
+        for path in paths {
+            self.states.remove(path);
+            self.deleted.remove(path);
+            if let Some(content) = states.get(path) {
+                self.states.insert(path.clone(), content.clone());
+            } else if deleted.contains(path) {
+                self.deleted.insert(path.clone());
+            }
+        }
+        self.pending_changes.retain(|change| !paths.contains(&change.path));
+    }
+
     /// Get state of a specific file
-    pub fn get_state(&self, path: &PathBuf) -> Option<&String> {
+    pub fn get_state(&self, path: &PathBuf) -> Option<&FileContent> {
         self.states.get(path)
     }
 
@@ -81,15 +384,29 @@ impl FileTracker {
         self.states.contains_key(path)
     }
 
+    /// Check if a file is known to be deleted
+    pub fn is_deleted(&self, path: &PathBuf) -> bool {
+        self.deleted.contains(path)
+    }
+
+    /// Check if a file was created by a tracked change
+    pub fn was_created(&self, path: &PathBuf) -> bool {
+        self.created.contains(path)
+    }
+
     /// Get number of tracked files
     pub fn tracked_count(&self) -> usize {
         self.states.len()
     }
 
+    fn is_known(&self, path: &PathBuf) -> bool {
+        self.states.contains_key(path) || self.deleted.contains(path) || self.created.contains(path)
+    }
+
     /// Calculate diff between two states
-    pub fn diff(
-        old: &HashMap<PathBuf, String>,
-        new: &HashMap<PathBuf, String>,
+    pub fn diff<T: PartialEq>(
+        old: &HashMap<PathBuf, T>,
+        new: &HashMap<PathBuf, T>,
     ) -> FileDiff {
         let mut added = Vec::new();
         let mut modified = Vec::new();
@@ -192,7 +509,7 @@ mod tests {
         );
         
         assert!(tracker.is_tracked(&PathBuf::from("/test.txt")));
-        assert_eq!(tracker.get_state(&PathBuf::from("/test.txt")), Some(&"new content".to_string()));
+        assert_eq!(tracker.get_state(&PathBuf::from("/test.txt")).unwrap(), "new content");
     }
 
     #[test]
@@ -217,7 +534,7 @@ mod tests {
         // Should still have 1 tracked file but 2 pending changes
         assert_eq!(tracker.tracked_count(), 1);
         assert_eq!(tracker.pending_changes().len(), 2);
-        assert_eq!(tracker.get_state(&PathBuf::from("/test.txt")), Some(&"second".to_string()));
+        assert_eq!(tracker.get_state(&PathBuf::from("/test.txt")).unwrap(), "second");
     }
 
     // === Pending Changes Tests ===
@@ -232,7 +549,7 @@ mod tests {
         assert_eq!(changes.len(), 1);
         assert_eq!(changes[0].path, PathBuf::from("/a.txt"));
         assert!(changes[0].old_content.is_none());
-        assert_eq!(changes[0].new_content, "content");
+        assert_eq!(changes[0].new_content, Some("content".into()));
     }
 
     #[test]
@@ -260,8 +577,8 @@ mod tests {
         
         let states = tracker.current_states();
         assert_eq!(states.len(), 2);
-        assert_eq!(states.get(&PathBuf::from("/a.txt")), Some(&"a".to_string()));
-        assert_eq!(states.get(&PathBuf::from("/b.txt")), Some(&"b".to_string()));
+        assert_eq!(states[&PathBuf::from("/a.txt")], "a");
+        assert_eq!(states[&PathBuf::from("/b.txt")], "b");
     }
 
     #[test]
@@ -270,7 +587,7 @@ mod tests {
         
         tracker.record_change(PathBuf::from("/test.txt"), None, "content".to_string());
         
-        assert_eq!(tracker.get_state(&PathBuf::from("/test.txt")), Some(&"content".to_string()));
+        assert_eq!(tracker.get_state(&PathBuf::from("/test.txt")).unwrap(), "content");
         assert!(tracker.get_state(&PathBuf::from("/nonexistent.txt")).is_none());
     }
 
@@ -294,9 +611,9 @@ mod tests {
         tracker.record_change(PathBuf::from("/b.txt"), None, "b".to_string());
         
         let mut new_states = HashMap::new();
-        new_states.insert(PathBuf::from("/c.txt"), "c".to_string());
+        new_states.insert(PathBuf::from("/c.txt"), FileContent::from("c"));
         
-        tracker.reset_to(&new_states);
+        tracker.reset_to(&new_states, &HashSet::new());
         
         assert_eq!(tracker.tracked_count(), 1);
         assert!(tracker.is_tracked(&PathBuf::from("/c.txt")));
@@ -439,7 +756,7 @@ mod tests {
         
         let change = &tracker.pending_changes()[0];
         assert!(change.old_content.is_none());
-        assert_eq!(change.new_content, "content");
+        assert_eq!(change.new_content, Some("content".into()));
     }
 
     #[test]
@@ -453,7 +770,157 @@ mod tests {
         );
         
         let change = &tracker.pending_changes()[0];
-        assert_eq!(change.old_content, Some("old".to_string()));
-        assert_eq!(change.new_content, "new");
+        assert_eq!(change.old_content, Some("old".into()));
+        assert_eq!(change.new_content, Some("new".into()));
+    }
+
+    // === Deletion Tests ===
+
+    #[test]
+    fn test_record_deletion() {
+        let mut tracker = FileTracker::new();
+        let path = PathBuf::from("/doomed.txt");
+        
+        tracker.record_change(path.clone(), Some("old".to_string()), "new".to_string());
+        tracker.record_deletion(path.clone(), None);
+        
+        assert!(!tracker.is_tracked(&path));
+        assert!(tracker.is_deleted(&path));
+        
+        let change = &tracker.pending_changes()[1];
+        assert_eq!(change.old_content, Some("new".into()));
+        assert!(change.new_content.is_none());
+    }
+
+    #[test]
+    fn test_recreate_deleted_file() {
+        let mut tracker = FileTracker::new();
+        let path = PathBuf::from("/phoenix.txt");
+        
+        tracker.record_deletion(path.clone(), Some("ashes".into()));
+        tracker.record_change(path.clone(), None, "reborn".to_string());
+        
+        assert!(tracker.is_tracked(&path));
+        assert!(!tracker.is_deleted(&path));
+        // It existed before the session, so it was not created by a tracked change
+        assert!(!tracker.was_created(&path));
+    }
+
+    #[test]
+    fn test_created_tracks_first_sighting() {
+        let mut tracker = FileTracker::new();
+        
+        tracker.record_change(PathBuf::from("/new.txt"), None, "a".to_string());
+        tracker.record_change(PathBuf::from("/old.txt"), Some("x".to_string()), "y".to_string());
+        
+        assert!(tracker.was_created(&PathBuf::from("/new.txt")));
+        assert!(!tracker.was_created(&PathBuf::from("/old.txt")));
+    }
+
+    #[test]
+    fn test_originals_from_first_change() {
+        let mut tracker = FileTracker::new();
+        let path = PathBuf::from("/old.txt");
+        
+        tracker.record_change(path.clone(), Some("v1".to_string()), "v2".to_string());
+        tracker.record_change(path.clone(), Some("v2".to_string()), "v3".to_string());
+        tracker.record_deletion(PathBuf::from("/gone.txt"), Some("bye".into()));
+        
+        assert_eq!(tracker.original_state(&path), Some(&FileContent::from("v1")));
+        assert_eq!(tracker.original_state(&PathBuf::from("/gone.txt")), Some(&FileContent::from("bye")));
+        
+        // Files created by a change have no original content
+        tracker.record_change(PathBuf::from("/new.txt"), None, "a".to_string());
+        assert_eq!(tracker.original_states().len(), 2);
+        
+        assert_eq!(tracker.pending_changes().len(), 4);
+        tracker.discard_pending(4);
+        assert!(tracker.pending_changes().is_empty());
+    }
+
+    #[test]
+    fn test_reset_keeps_created() {
+        let mut tracker = FileTracker::new();
+        let path = PathBuf::from("/new.txt");
+        
+        tracker.record_change(path.clone(), None, "a".to_string());
+        
+        let mut deleted = HashSet::new();
+        deleted.insert(PathBuf::from("/gone.txt"));
+        tracker.reset_to(&HashMap::new(), &deleted);
+        
+        assert!(!tracker.is_tracked(&path));
+        assert!(tracker.was_created(&path));
+        assert!(tracker.is_deleted(&PathBuf::from("/gone.txt")));
+    }
+
+    // === Binary Content Tests ===
+
+    #[test]
+    fn test_record_binary_change() {
+        let mut tracker = FileTracker::new();
+        let png = vec![0x89, b'P', b'N', b'G', 0x00, 0xff];
+        
+        tracker.record_binary_change(PathBuf::from("/logo.png"), None, png.clone());
+        
+        let state = tracker.get_state(&PathBuf::from("/logo.png")).unwrap();
+        assert_eq!(state.as_bytes(), png.as_slice());
+        assert!(!state.is_text());
+    }
+
+    #[test]
+    fn test_file_content_text() {
+        let content = FileContent::from("hello");
+        
+        assert_eq!(content.as_text(), Some("hello"));
+        assert_eq!(content.len(), 5);
+        assert_eq!(content, "hello");
+    }
+
+    #[test]
+    fn test_record_observed() {
+        let mut tracker = FileTracker::new();
+        let path = PathBuf::from("/test/file.rs");
+        
+        assert!(!tracker.record_observed(path.clone(), None, false));
+        assert!(tracker.record_observed(path.clone(), Some("v1".into()), true));
+        assert!(!tracker.record_observed(path.clone(), Some("v1".into()), false));
+        assert!(tracker.record_observed(path.clone(), Some("v2".into()), false));
+        
+        let last = &tracker.pending_changes()[1];
+        assert_eq!(last.old_content, Some(FileContent::from("v1")));
+        assert!(tracker.was_created(&path));
+        
+        assert!(tracker.record_observed(path.clone(), None, false));
+        assert!(tracker.is_deleted(&path));
+    }
+
+    #[test]
+    fn test_record_observed_unknown_existing_file() {
+        let mut tracker = FileTracker::new();
+        let path = PathBuf::from("/test/README.md");
+        
+        assert!(!tracker.record_observed(path.clone(), Some("edited by a shell".into()), false));
+        assert!(!tracker.was_created(&path));
+        assert!(tracker.get_state(&path).is_none());
+        
+        tracker.record_baseline(path.clone(), "original".into());
+        assert!(tracker.record_observed(path.clone(), Some("edited by a shell".into()), false));
+        assert!(!tracker.was_created(&path));
+        assert_eq!(tracker.pending_changes()[0].old_content, Some(FileContent::from("original")));
+    }
+
+    #[test]
+    fn test_record_baseline() {
+        let mut tracker = FileTracker::new();
+        let path = PathBuf::from("/test/file.rs");
+        
+        assert!(tracker.record_baseline(path.clone(), "original".into()));
+        assert!(tracker.pending_changes().is_empty());
+        assert!(!tracker.was_created(&path));
+        
+        tracker.record_change(path.clone(), Some("original".to_string()), "edited".to_string());
+        assert!(!tracker.record_baseline(path.clone(), "original".into()));
+        assert_eq!(tracker.get_state(&path), Some(&FileContent::from("edited")));
     }
 }
@@ -58,13 +58,32 @@ pub mod checkpoint;
 pub mod turn_tracker;
 pub mod file_tracker;
 pub mod storage;
+pub mod blob_store;
+pub mod diff;
+pub mod restore;
+pub mod merge;
+pub mod conversation;
+pub mod agent_state;
+pub mod pattern;
+mod scan;
+#[cfg(feature = "watch")]
+pub mod watcher;
 pub mod error;
 
-pub use manager::CheckpointManager;
-pub use checkpoint::{Checkpoint, CheckpointData};
+pub use manager::{CheckpointManager, CheckpointConfig};
+pub use checkpoint::{Checkpoint, CheckpointData, ChangeRecord};
 pub use turn_tracker::TurnTracker;
-pub use file_tracker::FileTracker;
-pub use error::CheckpointError;
+pub use file_tracker::{FileTracker, FileContent, FileChange, ChangeSource};
+pub use blob_store::{BlobStore, BlobHash, BlobRef};
+pub use diff::{CheckpointDiff, FileLineDiff};
+pub use restore::{ConflictPolicy, RestorePlan, RestoreReport, RestoreFailure, RevertReport};
+pub use merge::FileMerge;
+pub use pattern::{PathMatcher, TrackingFilter};
+#[cfg(feature = "watch")]
+pub use watcher::{FileWatcher, WatchConfig};
+pub use conversation::ConversationProvider;
+pub use agent_state::{AgentState, AgentStateRegistry, AgentStateRestore, AgentStateSnapshot};
+pub use error::{CheckpointError, HookError};
 
 // Re-export protocol types
 pub use warhorn::{CheckpointId, CheckpointMeta};
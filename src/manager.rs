@@ -1,21 +1,37 @@
 //! Checkpoint manager - coordinates checkpointing
 
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use parking_lot::RwLock;
-use chrono::{DateTime, Utc};
+use chrono::Utc;
 use tracing::{debug, info, warn};
 
 use warhorn::{CheckpointId, CheckpointMeta, TaskId};
-use crate::checkpoint::{Checkpoint, CheckpointData};
+use crate::checkpoint::{ChangeRecord, Checkpoint, CheckpointData, ConversationDelta};
+use crate::conversation::ConversationProvider;
+use crate::agent_state::AgentStateRegistry;
 use crate::turn_tracker::TurnTracker;
-use crate::file_tracker::FileTracker;
+use crate::file_tracker::{ChangeSource, FileChange, FileContent, FileTracker};
+use crate::storage::CheckpointStorage;
+use crate::blob_store::{BlobHash, BlobRef, BlobStore};
+use crate::diff::{CheckpointDiff, FileLineDiff, DEFAULT_CONTEXT_LINES};
+use crate::restore::{
+    read_disk, ConflictPolicy, RestorePlan, RestoreReport, RestoreTransaction, RevertReport,
+};
+use crate::merge::{merge3, revert, FileMerge, Revert};
+use crate::pattern::{absolute_path, PathMatcher, TrackingFilter};
+use crate::scan::scan_workspace;
 use crate::error::CheckpointError;
 
+/// Name given to workspace baseline checkpoints
+pub const BASELINE_NAME: &str = "workspace baseline";
+
 /// Configuration for checkpoint manager
 #[derive(Debug, Clone)]
 pub struct CheckpointConfig {
-    /// Storage directory for checkpoints
+    /// Storage directory for checkpoints; a relative path is resolved
+    /// against `workspace_root`, or the current directory without one
     pub storage_dir: PathBuf,
     /// Maximum number of checkpoints to retain
     pub max_checkpoints: usize,
@@ -23,6 +39,20 @@ pub struct CheckpointConfig {
     pub auto_checkpoint: bool,
     /// Track file changes
     pub track_files: bool,
+    /// How restore, undo and redo treat externally modified files
+    pub conflict_policy: ConflictPolicy,
+    /// Workspace root, used to find `.gitignore` and `.ignore` files and
+    /// to anchor relative include/exclude patterns. A relative root is
+    /// resolved against the current directory.
+    pub workspace_root: Option<PathBuf>,
+    /// Only track files matching these paths or globs (empty tracks everything)
+    pub include: Vec<String>,
+    /// Never track files matching these paths or globs. Relative ones only
+    /// apply below `workspace_root`, or the root given to
+    /// [`CheckpointManager::snapshot_workspace`].
+    pub exclude: Vec<String>,
+    /// Honor `.gitignore` and `.ignore` files
+    pub respect_ignore_files: bool,
 }
 
 impl Default for CheckpointConfig {
@@ -32,17 +62,66 @@ impl Default for CheckpointConfig {
             max_checkpoints: 50,
             auto_checkpoint: true,
             track_files: true,
+            conflict_policy: ConflictPolicy::Abort,
+            workspace_root: None,
+            include: Vec::new(),
+            exclude: vec![
+                ".git".to_string(),
+                "target".to_string(),
+                "node_modules".to_string(),
+            ],
+            respect_ignore_files: true,
         }
     }
 }
 
+impl CheckpointConfig {
+    /// Build the filter deciding which files are tracked. The storage
+    /// directory is always excluded.
+    fn tracking_filter(&self) -> Result<TrackingFilter, CheckpointError> {
+        let filter = TrackingFilter::new(
+            self.workspace_root.clone(),
+            &self.include,
+            &self.exclude,
+            self.storage_dir.clone(),
+        )?;
+        Ok(filter.respect_ignore_files(self.respect_ignore_files))
+    }
+
+    /// Like [`tracking_filter`](Self::tracking_filter), ignoring invalid patterns
+    fn lenient_tracking_filter(&self) -> TrackingFilter {
+        TrackingFilter::lenient(
+            self.workspace_root.clone(),
+            &self.include,
+            &self.exclude,
+            self.storage_dir.clone(),
+        ).respect_ignore_files(self.respect_ignore_files)
+    }
+
+    /// Resolve the workspace root and storage directory to absolute,
+    /// canonical paths so they prefix the paths recorded and reported
+    /// under them
+    fn resolve_paths(mut self) -> Self {
+        self.workspace_root = self.workspace_root.map(|root| absolute_path(&root));
+        self.storage_dir = match &self.workspace_root {
+            Some(root) => absolute_path(&root.join(&self.storage_dir)),
+            None => absolute_path(&self.storage_dir),
+        };
+        self
+    }
+}
+
 /// Manages checkpoints and undo functionality
 pub struct CheckpointManager {
     /// Configuration
     config: CheckpointConfig,
+    /// Persistent storage backend
+    storage: CheckpointStorage,
+    /// Content-addressed file contents shared by all checkpoints
+    blobs: BlobStore,
     /// All checkpoints
     checkpoints: RwLock<HashMap<CheckpointId, Checkpoint>>,
-    /// Checkpoint order (oldest first)
+    /// Checkpoint creation order (oldest first)
     order: RwLock<Vec<CheckpointId>>,
     /// Turn tracker
     turn_tracker: RwLock<TurnTracker>,
@@ -50,19 +129,165 @@ pub struct CheckpointManager {
     file_tracker: RwLock<FileTracker>,
     /// Current checkpoint (for undo)
     current: RwLock<Option<CheckpointId>>,
+    /// Tip of the active branch (for redo)
+    tip: RwLock<Option<CheckpointId>>,
+    /// On-disk size of each stored checkpoint
+    sizes: RwLock<HashMap<CheckpointId, u64>>,
+    /// Which files are tracked, scanned and restored
+    filter: TrackingFilter,
+    /// Host-supplied conversation snapshots
+    conversation: RwLock<Option<Arc<dyn ConversationProvider>>>,
+    /// Subsystems whose state is captured with each checkpoint
+    agent_states: AgentStateRegistry,
+    /// Stored checkpoints that could not be read when opening
+    unreadable: Vec<CheckpointId>,
 }
 
 impl CheckpointManager {
     /// Create a new checkpoint manager
+    ///
+    /// An include or exclude list with an invalid pattern is logged and
+    /// ignored; use [`try_new`](Self::try_new) to reject it instead.
     pub fn new(config: CheckpointConfig) -> Self {
+        let config = config.resolve_paths();
+        let filter = config.lenient_tracking_filter();
+        Self::with_filter(config, filter)
+    }
+
+    /// Create a new checkpoint manager, failing on invalid include or
+    /// exclude patterns
+    pub fn try_new(config: CheckpointConfig) -> Result<Self, CheckpointError> {
+        let config = config.resolve_paths();
+        let filter = config.tracking_filter()?;
+        Ok(Self::with_filter(config, filter))
+    }
+
+    fn with_filter(config: CheckpointConfig, filter: TrackingFilter) -> Self {
+        let storage = CheckpointStorage::new(config.storage_dir.clone());
+        let blobs = BlobStore::new(config.storage_dir.join("blobs"));
+
         Self {
             config,
+            storage,
+            blobs,
             checkpoints: RwLock::new(HashMap::new()),
             order: RwLock::new(Vec::new()),
             turn_tracker: RwLock::new(TurnTracker::new()),
             file_tracker: RwLock::new(FileTracker::new()),
             current: RwLock::new(None),
+            tip: RwLock::new(None),
+            sizes: RwLock::new(HashMap::new()),
+            filter,
+            conversation: RwLock::new(None),
+            agent_states: AgentStateRegistry::new(),
+            unreadable: Vec::new(),
+        }
+    }
+
+    /// Open a checkpoint manager, rehydrating any checkpoints already in storage
+    pub async fn open(config: CheckpointConfig) -> Result<Self, CheckpointError> {
+        let config = config.resolve_paths();
+        let filter = config.tracking_filter()?;
+        let mut manager = Self::with_filter(config, filter);
+        manager.storage.init().await?;
+
+        // A corrupt checkpoint file loses that checkpoint, not the session
+        let mut loaded = Vec::new();
+        for id in manager.storage.list_ids().await? {
+            let result = match manager.storage.load(&id).await {
+                Ok(checkpoint) => manager.storage.size(&id).await.map(|size| (checkpoint, size)),
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(entry) => loaded.push(entry),
+                Err(e) => {
+                    warn!(checkpoint_id = %id, error = %e, "Skipping unreadable checkpoint");
+                    manager.unreadable.push(id);
+                }
+            }
+        }
+        loaded.sort_by_key(|(checkpoint, _)| checkpoint.timestamp);
+
+        {
+            let mut checkpoints = manager.checkpoints.write();
+            let mut order = manager.order.write();
+            let mut sizes = manager.sizes.write();
+            let mut turn_tracker = manager.turn_tracker.write();
+
+            for (checkpoint, size) in loaded {
+                let id = checkpoint.id;
+                if let (Some(task_id), Some(turn_number)) = (checkpoint.task_id, checkpoint.turn_number) {
+                    turn_tracker.record_turn(task_id, turn_number, id);
+                }
+                for blob in checkpoint.data.blob_refs() {
+                    manager.blobs.retain(&blob.hash);
+                }
+                order.push(id);
+                sizes.insert(id, size);
+                checkpoints.insert(id, checkpoint);
+            }
+
+            Self::expand_conversations(&mut checkpoints);
+        }
+
+        // Fall back to the newest checkpoint if the head pointer is missing or stale
+        let current = manager.storage.load_head().await?
+            .filter(|id| manager.checkpoints.read().contains_key(id))
+            .or_else(|| manager.order.read().last().copied());
+
+        let original_states: HashMap<PathBuf, BlobRef> = manager.checkpoints.read()
+            .values()
+            .flat_map(|c| c.data.original_states.clone())
+            .collect();
+        // Likewise a missing blob loses that file's content, not the session
+        let mut originals = HashMap::new();
+        for (path, blob) in original_states {
+            match manager.blobs.get(&blob.hash).await {
+                Ok(content) => {
+                    originals.insert(path, FileContent::from_bytes(content));
+                }
+                Err(e) => warn!(path = %path.display(), error = %e, "Skipping unreadable original state"),
+            }
         }
+        manager.file_tracker.write().add_originals(originals);
+
+        if let Some(id) = current {
+            let data = manager.checkpoints.read().get(&id).map(|c| c.data.clone());
+            if let Some(data) = data {
+                let mut states = manager.unchanged_originals(&data);
+                for (path, blob) in &data.file_states {
+                    match manager.blobs.get(&blob.hash).await {
+                        Ok(content) => {
+                            states.insert(path.clone(), FileContent::from_bytes(content));
+                        }
+                        Err(e) => warn!(
+                            checkpoint_id = %id,
+                            path = %path.display(),
+                            error = %e,
+                            "Skipping unreadable file state"
+                        ),
+                    }
+                }
+                let mut file_tracker = manager.file_tracker.write();
+                file_tracker.reset_to(&states, &data.deleted_files);
+                file_tracker.mark_created(data.created_files);
+            }
+            *manager.current.write() = Some(id);
+        }
+
+        let tip = manager.storage.load_tip().await?
+            .filter(|id| manager.checkpoints.read().contains_key(id))
+            .or(current);
+        *manager.tip.write() = tip;
+
+        info!(
+            dir = %manager.config.storage_dir.display(),
+            count = manager.count(),
+            current = ?current,
+            "Opened checkpoint storage"
+        );
+
+        Ok(manager)
     }
 
     /// Save a manual checkpoint
@@ -79,15 +304,11 @@ impl CheckpointManager {
             timestamp,
             task_id: None,
             turn_number: None,
+            parent: *self.current.read(),
             data,
         };
 
-        let size = self.store_checkpoint(&checkpoint).await?;
-        
-        // Add to registry
-        self.checkpoints.write().insert(id, checkpoint);
-        self.order.write().push(id);
-        *self.current.write() = Some(id);
+        let size = self.register_checkpoint(checkpoint).await?;
 
         // Prune old checkpoints
         self.prune_checkpoints().await?;
@@ -102,6 +323,44 @@ impl CheckpointManager {
         Ok(id)
     }
 
+    /// Scan a workspace and save a baseline checkpoint of every file in it
+    ///
+    /// Call at session start so the checkpoint can restore any file to its
+    /// pre-session state, even if no change to it recorded the old content.
+    /// Files the configured filter does not track, including those ignored
+    /// by `.gitignore` and `.ignore` files under `root`, and files matching
+    /// `exclude` (paths or globs, see [`PathMatcher`]) are skipped; files
+    /// hutch already tracks keep their recorded state. The baseline is named, so it is
+    /// never pruned.
+    pub async fn snapshot_workspace<I, S>(
+        &self,
+        root: impl AsRef<Path>,
+        exclude: I,
+    ) -> Result<CheckpointId, CheckpointError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let root = root.as_ref();
+        let exclude = PathMatcher::under(Some(root), exclude)?;
+        let filter = self.filter.or_root(root);
+        let files = scan_workspace(root, &filter, &exclude).await?;
+
+        let mut added = 0;
+        for path in files {
+            let Some(content) = read_disk(&path).await? else {
+                continue;
+            };
+            if self.file_tracker.write().record_baseline(path, FileContent::from_bytes(content)) {
+                added += 1;
+            }
+        }
+
+        info!(root = %root.display(), files = added, "Captured workspace baseline");
+
+        self.save(Some(BASELINE_NAME.to_string())).await
+    }
+
     /// Checkpoint at turn boundary (auto-checkpoint)
     pub async fn checkpoint_turn(
         &self,
@@ -124,18 +383,14 @@ impl CheckpointManager {
             timestamp,
             task_id: Some(task_id),
             turn_number: Some(turn_number),
+            parent: *self.current.read(),
             data,
         };
 
-        let size = self.store_checkpoint(&checkpoint).await?;
-        
-        // Add to registry
-        self.checkpoints.write().insert(id, checkpoint);
-        self.order.write().push(id);
-        *self.current.write() = Some(id);
+        let size = self.register_checkpoint(checkpoint).await?;
 
         // Update turn tracker
-        self.turn_tracker.write().record_turn(turn_number, id);
+        self.turn_tracker.write().record_turn(task_id, turn_number, id);
 
         // Prune old checkpoints
         self.prune_checkpoints().await?;
@@ -144,64 +399,455 @@ impl CheckpointManager {
             checkpoint_id = %id,
             task_id = %task_id,
             turn = turn_number,
+            size = size,
             "Saved turn checkpoint"
         );
 
         Ok(id)
     }
 
-    /// Undo to the last checkpoint
+    /// Undo to the parent of the current checkpoint
     pub async fn undo(&self) -> Result<CheckpointId, CheckpointError> {
-        let current = self.current.read().clone();
-        
-        // Find the previous checkpoint
-        let order = self.order.read();
-        let current_idx = current
-            .and_then(|id| order.iter().position(|&i| i == id))
-            .unwrap_or(order.len());
-        
-        if current_idx == 0 {
-            return Err(CheckpointError::NothingToUndo);
-        }
+        let target_id = self.undo_target().ok_or(CheckpointError::NothingToUndo)?;
+        self.restore_checkpoint(target_id, self.config.conflict_policy, true).await?;
+
+        Ok(target_id)
+    }
+
+    /// Redo towards the tip of the active branch
+    pub async fn redo(&self) -> Result<CheckpointId, CheckpointError> {
+        let target_id = self.redo_target().ok_or(CheckpointError::NothingToRedo)?;
+        self.restore_checkpoint(target_id, self.config.conflict_policy, true).await?;
+
+        Ok(target_id)
+    }
 
-        let target_id = order[current_idx - 1];
-        drop(order);
+    /// Check whether there is a checkpoint to undo to
+    pub fn can_undo(&self) -> bool {
+        self.undo_target().is_some()
+    }
 
-        self.restore(target_id).await
+    /// Check whether there is a checkpoint to redo to
+    pub fn can_redo(&self) -> bool {
+        self.redo_target().is_some()
     }
 
     /// Restore a specific checkpoint
+    ///
+    /// Restoring an ancestor of the active branch tip keeps that branch
+    /// redoable; restoring anything else makes it the new tip.
     pub async fn restore(&self, checkpoint_id: CheckpointId) -> Result<CheckpointId, CheckpointError> {
-        let checkpoint = self.checkpoints.read()
-            .get(&checkpoint_id)
-            .cloned()
+        self.restore_with_policy(checkpoint_id, self.config.conflict_policy).await?;
+
+        Ok(checkpoint_id)
+    }
+
+    /// Restore a specific checkpoint, choosing how to treat files that
+    /// were modified outside of hutch since it last recorded them
+    pub async fn restore_with_policy(
+        &self,
+        checkpoint_id: CheckpointId,
+        policy: ConflictPolicy,
+    ) -> Result<RestoreReport, CheckpointError> {
+        self.restore_on_branch(checkpoint_id, policy, true).await
+    }
+
+    /// Restore a checkpoint's files and agent states but keep the current
+    /// conversation, for when the edits went wrong but the discussion did not
+    pub async fn restore_files_only(&self, checkpoint_id: CheckpointId) -> Result<RestoreReport, CheckpointError> {
+        self.restore_on_branch(checkpoint_id, self.config.conflict_policy, false).await
+    }
+
+    /// Rewind only the conversation to the snapshot taken at a task's
+    /// turn, leaving the workspace and the current checkpoint alone
+    pub async fn rewind_conversation(
+        &self,
+        task_id: TaskId,
+        turn_number: u32,
+    ) -> Result<CheckpointId, CheckpointError> {
+        let checkpoint_id = self.turn_tracker.read()
+            .checkpoint_for_task_turn(task_id, turn_number)
+            .ok_or(CheckpointError::TurnNotFound(task_id, turn_number))?;
+        let snapshot = self.get(&checkpoint_id)
+            .ok_or(CheckpointError::NotFound(checkpoint_id))?
+            .data
+            .conversation_snapshot
+            .ok_or(CheckpointError::NoConversationSnapshot(checkpoint_id))?;
+        let provider = self.conversation_provider()
+            .ok_or(CheckpointError::NoConversationProvider)?;
+
+        provider.restore(&snapshot)
+            .map_err(|e| CheckpointError::ConversationError(e.to_string()))?;
+
+        info!(
+            checkpoint_id = %checkpoint_id,
+            turn = turn_number,
+            messages = snapshot.messages.len(),
+            "Rewound conversation"
+        );
+
+        Ok(checkpoint_id)
+    }
+
+    /// Restore only the files matching `patterns` to their state at a
+    /// checkpoint.
+    ///
+    /// Patterns are paths or globs; relative ones are resolved against
+    /// `workspace_root` and fail with [`CheckpointError::NoWorkspaceRoot`]
+    /// if it is not set. A directory selects everything under it.
+    /// Everything else, including the current checkpoint, is left as is.
+    pub async fn restore_paths<I, S>(
+        &self,
+        checkpoint_id: CheckpointId,
+        patterns: I,
+    ) -> Result<RestoreReport, CheckpointError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let matcher = PathMatcher::rooted(self.config.workspace_root.as_deref(), patterns)?;
+        let checkpoint = self.get(&checkpoint_id)
             .ok_or(CheckpointError::NotFound(checkpoint_id))?;
 
+        let report = self.restore_files(
+            checkpoint_id,
+            &checkpoint.data,
+            self.config.conflict_policy,
+            Some(&matcher),
+        ).await?;
+
         info!(
             checkpoint_id = %checkpoint_id,
-            name = ?checkpoint.name,
-            "Restoring checkpoint"
+            files = report.changed.len(),
+            "Restored paths from checkpoint"
         );
 
-        // Restore file state
-        self.restore_files(&checkpoint.data).await?;
+        Ok(report)
+    }
 
-        // Update current
-        *self.current.write() = Some(checkpoint_id);
+    /// Preview what restoring a checkpoint would change, without touching any files
+    pub async fn plan_restore(&self, checkpoint_id: CheckpointId) -> Result<RestorePlan, CheckpointError> {
+        let checkpoint = self.get(&checkpoint_id)
+            .ok_or(CheckpointError::NotFound(checkpoint_id))?;
+        let mut plan = RestorePlan::new(checkpoint_id);
 
-        Ok(checkpoint_id)
+        let originals = self.unchanged_originals(&checkpoint.data)
+            .into_iter()
+            .map(|(path, content)| (path, BlobRef::for_content(content.as_bytes())));
+        let writes: Vec<(PathBuf, BlobRef)> = checkpoint.data.file_states.clone()
+            .into_iter()
+            .chain(originals)
+            .collect();
+
+        for (path, blob) in writes {
+            if !self.should_track(&path) {
+                continue;
+            }
+            let on_disk = read_disk(&path).await?;
+            match &on_disk {
+                Some(content) if BlobHash::of(content) == blob.hash => continue,
+                Some(_) => plan.overwrite.push(path.clone()),
+                None => plan.create.push(path.clone()),
+            }
+            plan.bytes_to_write += blob.size;
+            if self.is_externally_modified(&path, on_disk.as_deref()) {
+                plan.externally_modified.push(path);
+            }
+        }
+
+        for path in self.files_to_remove(&checkpoint.data) {
+            if !self.should_track(&path) {
+                continue;
+            }
+            let Some(content) = read_disk(&path).await? else {
+                continue;
+            };
+            plan.bytes_to_delete += content.len() as u64;
+            if self.is_externally_modified(&path, Some(&content)) {
+                plan.externally_modified.push(path.clone());
+            }
+            plan.delete.push(path);
+        }
+
+        plan.sort();
+        Ok(plan)
+    }
+
+    /// Revert only the changes made during one of a task's turns, keeping
+    /// later turns of every task.
+    ///
+    /// The changes between the turn's checkpoint and its parent are undone
+    /// in the current files. Where later turns edited the same files, the
+    /// inverse is three-way merged and overlapping lines get conflict
+    /// markers; files that cannot be merged are skipped. The reverted
+    /// files are recorded as pending changes for the next checkpoint. If
+    /// the parent was pruned, the turn's change journal stands in for it.
+    pub async fn revert_turn(
+        &self,
+        task_id: TaskId,
+        turn_number: u32,
+    ) -> Result<RevertReport, CheckpointError> {
+        let checkpoint_id = self.turn_tracker.read()
+            .checkpoint_for_task_turn(task_id, turn_number)
+            .ok_or(CheckpointError::TurnNotFound(task_id, turn_number))?;
+        self.revert_checkpoint(turn_number, checkpoint_id).await
+    }
+
+    /// Undo a task's latest turn, leaving other tasks' work alone.
+    ///
+    /// The turn is reverted as by [`revert_turn`](Self::revert_turn), and
+    /// repeated calls walk back through the task's earlier turns.
+    pub async fn undo_task(&self, task_id: TaskId) -> Result<RevertReport, CheckpointError> {
+        let (turn_number, checkpoint_id) = self.turn_tracker.read()
+            .current_task_turn(task_id)
+            .ok_or(CheckpointError::NothingToUndo)?;
+        let report = self.revert_checkpoint(turn_number, checkpoint_id).await?;
+        self.turn_tracker.write().rewind_task(task_id);
+
+        Ok(report)
+    }
+
+    /// All turns recorded for a task with their checkpoints, in turn order
+    pub fn task_turns(&self, task_id: TaskId) -> Vec<(u32, CheckpointId)> {
+        self.turn_tracker.read().task_turns(task_id)
+    }
+
+    /// Checkpoint of the turn before a task's current one
+    pub fn previous_task_checkpoint(&self, task_id: TaskId) -> Option<CheckpointId> {
+        self.turn_tracker.read().previous_task_checkpoint(task_id)
+    }
+
+    async fn revert_checkpoint(
+        &self,
+        turn_number: u32,
+        checkpoint_id: CheckpointId,
+    ) -> Result<RevertReport, CheckpointError> {
+        let after = self.get(&checkpoint_id)
+            .ok_or(CheckpointError::NotFound(checkpoint_id))?;
+        // A pruned parent is rebuilt from the changes journaled since it
+        let before = match after.parent {
+            Some(parent) => self.get(&parent)
+                .map(|parent| parent.data)
+                .unwrap_or_else(|| after.data.before_changes()),
+            None => CheckpointData::default(),
+        };
+
+        let mut paths: Vec<&PathBuf> = before.file_states.keys()
+            .chain(after.data.file_states.keys())
+            .filter(|path| self.should_track(path))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        paths.sort();
+
+        // Files first changed by this turn had their original content before it
+        let originals = self.unchanged_originals(&before);
+
+        let mut transaction = RestoreTransaction::new();
+        let mut reverted = Vec::new();
+        let mut merged = Vec::new();
+        let mut skipped = Vec::new();
+        
+        for path in paths {
+            let old_blob = before.file_states.get(path);
+            let new_blob = after.data.file_states.get(path);
+            if old_blob.map(|b| &b.hash) == new_blob.map(|b| &b.hash) {
+                continue;
+            }
+
+            let old_content = match old_blob {
+                Some(blob) => Some(self.blobs.get(&blob.hash).await?),
+                None => originals.get(path).map(|c| c.as_bytes().to_vec()),
+            };
+            let new_content = match new_blob {
+                Some(blob) => Some(self.blobs.get(&blob.hash).await?),
+                None => None,
+            };
+            let current = read_disk(path).await?;
+
+            match revert(old_content.as_deref(), new_content.as_deref(), current.as_deref()) {
+                Revert::Unchanged => {}
+                Revert::Unmergeable => skipped.push(path.clone()),
+                Revert::Replace { content, merged: conflicts } => {
+                    match &content {
+                        Some(content) => transaction.write(path.clone(), content.clone()).await?,
+                        None => transaction.remove(path.clone()).await?,
+                    }
+                    if let Some(conflicts) = conflicts {
+                        merged.push(FileMerge { path: path.clone(), conflicts });
+                    }
+                    reverted.push((path.clone(), current, content));
+                }
+            }
+        }
+
+        let changed = transaction.apply().await
+            .map_err(CheckpointError::RestoreFailed)?;
+        
+        {
+            let mut file_tracker = self.file_tracker.write();
+            for (path, old_content, new_content) in reverted {
+                let old_content = old_content.map(FileContent::from_bytes);
+                match new_content {
+                    Some(content) => file_tracker.record_content_change(
+                        path,
+                        old_content,
+                        FileContent::from_bytes(content),
+                    ),
+                    None => file_tracker.record_deletion(path, old_content),
+                }
+            }
+        }
+
+        info!(
+            turn = turn_number,
+            files = changed.len(),
+            merged = merged.len(),
+            skipped = skipped.len(),
+            "Reverted turn"
+        );
+
+        Ok(RevertReport { turn_number, checkpoint_id, changed, merged, skipped })
+    }
+
+    /// Switch to another branch by restoring its tip
+    pub async fn switch_branch(&self, tip: CheckpointId) -> Result<CheckpointId, CheckpointError> {
+        self.restore_checkpoint(tip, self.config.conflict_policy, true).await?;
+        self.set_tip(tip).await?;
+
+        Ok(tip)
+    }
+
+    /// List the tips of all branches (checkpoints without children), oldest first
+    pub fn branches(&self) -> Vec<CheckpointMeta> {
+        let parents: HashSet<CheckpointId> = self.checkpoints.read()
+            .values()
+            .filter_map(|c| c.parent)
+            .collect();
+        
+        self.list()
+            .into_iter()
+            .filter(|meta| !parents.contains(&meta.id))
+            .collect()
+    }
+
+    /// Get the children of a checkpoint, oldest first
+    pub fn children(&self, id: &CheckpointId) -> Vec<CheckpointId> {
+        let checkpoints = self.checkpoints.read();
+        let order = self.order.read();
+        
+        order.iter()
+            .filter(|child| checkpoints.get(child).is_some_and(|c| c.parent == Some(*id)))
+            .copied()
+            .collect()
+    }
+
+    /// Get the path from the root of the history tree to a checkpoint
+    pub fn path_to(&self, id: &CheckpointId) -> Result<Vec<CheckpointId>, CheckpointError> {
+        if !self.checkpoints.read().contains_key(id) {
+            return Err(CheckpointError::NotFound(*id));
+        }
+
+        let mut path = self.ancestors(*id);
+        path.reverse();
+        Ok(path)
+    }
+
+    /// Find the most recent checkpoint that both checkpoints descend from
+    pub fn common_ancestor(
+        &self,
+        a: &CheckpointId,
+        b: &CheckpointId,
+    ) -> Result<Option<CheckpointId>, CheckpointError> {
+        let path_a = self.path_to(a)?;
+        let path_b = self.path_to(b)?;
+        
+        Ok(path_a.iter()
+            .zip(path_b.iter())
+            .take_while(|(x, y)| x == y)
+            .last()
+            .map(|(id, _)| *id))
+    }
+
+    /// Compute line-level diffs of tracked files between two checkpoints
+    pub async fn diff(
+        &self,
+        from: &CheckpointId,
+        to: &CheckpointId,
+    ) -> Result<CheckpointDiff, CheckpointError> {
+        let old = self.get(from).ok_or(CheckpointError::NotFound(*from))?;
+        let new = self.get(to).ok_or(CheckpointError::NotFound(*to))?;
+
+        let mut paths: Vec<&PathBuf> = old.data.file_states.keys()
+            .chain(new.data.file_states.keys())
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        paths.sort();
+
+        // Files a checkpoint knows nothing about still had their original content
+        let old_originals = self.unchanged_originals(&old.data);
+        let new_originals = self.unchanged_originals(&new.data);
+
+        let mut files = Vec::new();
+        for path in paths {
+            let old_blob = old.data.file_states.get(path);
+            let new_blob = new.data.file_states.get(path);
+            if old_blob.map(|b| &b.hash) == new_blob.map(|b| &b.hash) {
+                continue;
+            }
+
+            let old_content = match old_blob {
+                Some(blob) => Some(self.blobs.get(&blob.hash).await?),
+                None => old_originals.get(path).map(|c| c.as_bytes().to_vec()),
+            };
+            let new_content = match new_blob {
+                Some(blob) => Some(self.blobs.get(&blob.hash).await?),
+                None => new_originals.get(path).map(|c| c.as_bytes().to_vec()),
+            };
+            if old_content == new_content {
+                continue;
+            }
+
+            files.push(FileLineDiff::compute(
+                path.clone(),
+                old_content.as_deref(),
+                new_content.as_deref(),
+                DEFAULT_CONTEXT_LINES,
+            ));
+        }
+
+        Ok(CheckpointDiff { from: *from, to: *to, files })
+    }
+
+    /// Get the tip of the active branch
+    pub fn tip(&self) -> Option<CheckpointId> {
+        *self.tip.read()
     }
 
     /// List all checkpoints
     pub fn list(&self) -> Vec<CheckpointMeta> {
         let checkpoints = self.checkpoints.read();
         let order = self.order.read();
+        let sizes = self.sizes.read();
         
         order.iter()
-            .filter_map(|id| checkpoints.get(id).map(|c| c.to_meta()))
+            .filter_map(|id| checkpoints.get(id).map(|c| {
+                let mut meta = c.to_meta();
+                if let Some(&size) = sizes.get(id) {
+                    meta.size_bytes = size;
+                }
+                meta
+            }))
             .collect()
     }
 
+    /// Stored checkpoints skipped by [`open`](Self::open) because they
+    /// could not be read
+    pub fn unreadable_checkpoints(&self) -> &[CheckpointId] {
+        &self.unreadable
+    }
+
     /// Get a checkpoint by ID
     pub fn get(&self, id: &CheckpointId) -> Option<Checkpoint> {
         self.checkpoints.read().get(id).cloned()
@@ -219,99 +865,2197 @@ impl CheckpointManager {
 
     /// Record a file change for tracking
     pub fn record_file_change(&self, path: PathBuf, old_content: Option<String>, new_content: String) {
-        self.file_tracker.write().record_change(path, old_content, new_content);
+        if self.should_track(&path) {
+            self.file_tracker.write().record_change(path, old_content, new_content);
+        }
     }
 
-    // === Private Methods ===
+    /// Record a file deletion for tracking
+    pub fn record_file_deletion(&self, path: PathBuf, old_content: Option<FileContent>) {
+        if self.should_track(&path) {
+            self.file_tracker.write().record_deletion(path, old_content);
+        }
+    }
 
-    async fn collect_checkpoint_data(&self) -> Result<CheckpointData, CheckpointError> {
-        let file_tracker = self.file_tracker.read();
-        
-        Ok(CheckpointData {
-            file_states: file_tracker.current_states(),
-            conversation_snapshot: None, // TODO: Implement
-            agent_states: HashMap::new(), // TODO: Implement
-        })
+    /// Record a change made by a tool, for the change journal.
+    /// `new_content` is `None` if the tool deleted the file.
+    pub fn record_tool_change(
+        &self,
+        path: PathBuf,
+        old_content: Option<FileContent>,
+        new_content: Option<FileContent>,
+        source: ChangeSource,
+    ) {
+        if !self.should_track(&path) {
+            return;
+        }
+
+        let mut file_tracker = self.file_tracker.write();
+        match new_content {
+            Some(content) => file_tracker.record_content_change_from(path, old_content, content, source),
+            None => file_tracker.record_deletion_from(path, old_content, source),
+        }
     }
 
-    async fn store_checkpoint(&self, checkpoint: &Checkpoint) -> Result<u64, CheckpointError> {
-        // In a full implementation, this would persist to disk
-        // For now, just calculate approximate size
-        let json = serde_json::to_string(&checkpoint)
-            .map_err(|e| CheckpointError::StorageError(e.to_string()))?;
-        
-        Ok(json.len() as u64)
+    /// Record a file's current on-disk content (`None` if it was removed),
+    /// diffing against the last known state. Returns whether it changed.
+    ///
+    /// `created` says whether the file was seen being created. Files hutch
+    /// has not recorded before are skipped unless they were created, since
+    /// their earlier content is unknown; snapshot the workspace first to
+    /// track edits to existing files.
+    pub fn record_observed_change(&self, path: PathBuf, content: Option<Vec<u8>>, created: bool) -> bool {
+        self.should_track(&path)
+            && self.file_tracker.write().record_observed(path, content.map(FileContent::from_bytes), created)
     }
 
-    async fn restore_files(&self, data: &CheckpointData) -> Result<(), CheckpointError> {
-        for (path, content) in &data.file_states {
-            tokio::fs::write(path, content).await
-                .map_err(|e| CheckpointError::RestoreError(format!(
-                    "Failed to restore {}: {}", path.display(), e
-                )))?;
-        }
-        
-        // Update file tracker
-        self.file_tracker.write().reset_to(&data.file_states);
-        
-        Ok(())
+    /// Check whether a file passes the configured include and exclude
+    /// patterns and ignore files. Changes to other files are dropped.
+    pub fn should_track(&self, path: &Path) -> bool {
+        self.filter.is_tracked(path)
     }
 
-    async fn prune_checkpoints(&self) -> Result<(), CheckpointError> {
-        let mut order = self.order.write();
-        let mut checkpoints = self.checkpoints.write();
-        
-        while order.len() > self.config.max_checkpoints {
-            if let Some(oldest_id) = order.first().copied() {
-                // Don't remove named checkpoints
-                if let Some(checkpoint) = checkpoints.get(&oldest_id) {
-                    if checkpoint.name.is_some() {
-                        // Skip named checkpoints
-                        order.remove(0);
-                        continue;
-                    }
-                }
-                
-                order.remove(0);
-                checkpoints.remove(&oldest_id);
-                debug!(checkpoint_id = %oldest_id, "Pruned old checkpoint");
-            }
-        }
-        
-        Ok(())
+    /// Get the changes recorded between a checkpoint's parent and the checkpoint, oldest first
+    pub fn journal(&self, id: &CheckpointId) -> Result<Vec<ChangeRecord>, CheckpointError> {
+        self.checkpoints.read()
+            .get(id)
+            .map(|c| c.data.changes.clone())
+            .ok_or(CheckpointError::NotFound(*id))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Register the provider whose conversation snapshots are stored in
+    /// new checkpoints and rewound on restore
+    pub fn set_conversation_provider(&self, provider: Arc<dyn ConversationProvider>) {
+        *self.conversation.write() = Some(provider);
+    }
 
-    #[tokio::test]
-    async fn test_manager_creation() {
-        let config = CheckpointConfig::default();
-        let manager = CheckpointManager::new(config);
-        assert_eq!(manager.count(), 0);
+    /// Remove the conversation provider
+    pub fn clear_conversation_provider(&self) {
+        *self.conversation.write() = None;
     }
 
-    #[tokio::test]
-    async fn test_save_checkpoint() {
-        let config = CheckpointConfig::default();
-        let manager = CheckpointManager::new(config);
-        
-        let id = manager.save(Some("test".to_string())).await.unwrap();
-        assert_eq!(manager.count(), 1);
-        assert_eq!(manager.current(), Some(id));
+    /// Get the registry of agent state participants, to register subsystems
+    /// whose state is captured with each checkpoint and replayed on restore
+    pub fn agent_states(&self) -> &AgentStateRegistry {
+        &self.agent_states
     }
 
-    #[tokio::test]
-    async fn test_list_checkpoints() {
-        let config = CheckpointConfig::default();
-        let manager = CheckpointManager::new(config);
-        
-        manager.save(Some("first".to_string())).await.unwrap();
-        manager.save(Some("second".to_string())).await.unwrap();
-        
-        let list = manager.list();
-        assert_eq!(list.len(), 2);
+    /// Get changes recorded since the last checkpoint, oldest first
+    pub fn pending_changes(&self) -> Vec<FileChange> {
+        self.file_tracker.read().pending_changes().to_vec()
+    }
+
+    /// Get every journaled change to a file, with the checkpoint that
+    /// recorded it, in checkpoint creation order
+    pub fn file_history(&self, path: &Path) -> Vec<(CheckpointId, ChangeRecord)> {
+        let checkpoints = self.checkpoints.read();
+        let order = self.order.read();
+
+        order.iter()
+            .filter_map(|id| checkpoints.get(id))
+            .flat_map(|c| {
+                c.data.changes.iter()
+                    .filter(|change| change.path == path)
+                    .map(|change| (c.id, change.clone()))
+            })
+            .collect()
+    }
+
+    /// Compute the line-level diff of a journaled change
+    pub async fn change_diff(&self, change: &ChangeRecord) -> Result<FileLineDiff, CheckpointError> {
+        let old_content = match &change.old_content {
+            Some(blob) => Some(self.blobs.get(&blob.hash).await?),
+            None => None,
+        };
+        let new_content = match &change.new_content {
+            Some(blob) => Some(self.blobs.get(&blob.hash).await?),
+            None => None,
+        };
+
+        Ok(FileLineDiff::compute(
+            change.path.clone(),
+            old_content.as_deref(),
+            new_content.as_deref(),
+            DEFAULT_CONTEXT_LINES,
+        ))
+    }
+
+    /// Get the last known content of a tracked file
+    pub fn file_state(&self, path: &PathBuf) -> Option<FileContent> {
+        self.file_tracker.read().get_state(path).cloned()
+    }
+
+    /// Get the content a file had before its first tracked change
+    /// (`None` if it was created by a tracked change or is unknown)
+    pub fn original_state(&self, path: &PathBuf) -> Option<FileContent> {
+        self.file_tracker.read().original_state(path).cloned()
+    }
+
+    /// Get the checkpoint storage directory, as an absolute path
+    pub fn storage_dir(&self) -> &Path {
+        &self.config.storage_dir
+    }
+
+    /// Record a binary file change for tracking
+    pub fn record_binary_file_change(&self, path: PathBuf, old_content: Option<Vec<u8>>, new_content: Vec<u8>) {
+        if self.should_track(&path) {
+            self.file_tracker.write().record_binary_change(path, old_content, new_content);
+        }
+    }
+
+    // === Private Methods ===
+
+    /// Snapshot tracked state, moving pending changes into the checkpoint's
+    /// change journal
+    async fn collect_checkpoint_data(&self) -> Result<CheckpointData, CheckpointError> {
+        let (states, deleted_files, created_files, originals, pending) = {
+            let file_tracker = self.file_tracker.read();
+            (
+                file_tracker.current_states(),
+                file_tracker.deleted_paths(),
+                file_tracker.created_paths(),
+                file_tracker.original_states().clone(),
+                file_tracker.pending_changes().to_vec(),
+            )
+        };
+        
+        let mut file_states = HashMap::new();
+        for (path, content) in states {
+            let blob = self.blobs.put(content.as_bytes()).await?;
+            file_states.insert(path, blob);
+        }
+
+        let mut original_states = HashMap::new();
+        for (path, content) in originals {
+            let blob = self.blobs.put(content.as_bytes()).await?;
+            original_states.insert(path, blob);
+        }
+
+        let mut changes = Vec::with_capacity(pending.len());
+        for change in pending {
+            let old_content = match &change.old_content {
+                Some(content) => Some(self.blobs.put(content.as_bytes()).await?),
+                None => None,
+            };
+            let new_content = match &change.new_content {
+                Some(content) => Some(self.blobs.put(content.as_bytes()).await?),
+                None => None,
+            };
+            changes.push(ChangeRecord {
+                path: change.path,
+                old_content,
+                new_content,
+                timestamp: change.timestamp,
+                source: change.source,
+            });
+        }
+        
+        let agent_states = self.agent_states.snapshot_all();
+        Ok(CheckpointData {
+            file_states,
+            deleted_files,
+            created_files,
+            original_states,
+            changes,
+            conversation_snapshot: self.conversation_provider().map(|p| p.snapshot()),
+            conversation_delta: None,
+            agent_states: agent_states.states,
+            agent_state_errors: agent_states.failed,
+        })
+    }
+
+    /// Store a new checkpoint as a child of the current one and make it
+    /// the current checkpoint and branch tip
+    async fn register_checkpoint(&self, checkpoint: Checkpoint) -> Result<u64, CheckpointError> {
+        let id = checkpoint.id;
+        let size = self.store_checkpoint(&checkpoint).await?;
+
+        // Only now that the journal is stored can it leave the tracker;
+        // changes recorded meanwhile stay pending
+        self.file_tracker.write().discard_pending(checkpoint.data.changes.len());
+
+        for blob in checkpoint.data.blob_refs() {
+            self.blobs.retain(&blob.hash);
+        }
+
+        // Add to registry
+        self.checkpoints.write().insert(id, checkpoint);
+        self.order.write().push(id);
+        self.sizes.write().insert(id, size);
+        self.set_current(id).await?;
+        self.set_tip(id).await?;
+
+        Ok(size)
+    }
+
+    /// Restore a checkpoint, keeping the active branch redoable if the
+    /// checkpoint is on it and making it the new tip otherwise
+    async fn restore_on_branch(
+        &self,
+        checkpoint_id: CheckpointId,
+        policy: ConflictPolicy,
+        rewind_conversation: bool,
+    ) -> Result<RestoreReport, CheckpointError> {
+        let report = self.restore_checkpoint(checkpoint_id, policy, rewind_conversation).await?;
+
+        let tip = *self.tip.read();
+        let on_active_branch = tip
+            .is_some_and(|tip| self.ancestors(tip).contains(&checkpoint_id));
+        if !on_active_branch {
+            self.set_tip(checkpoint_id).await?;
+        }
+
+        Ok(report)
+    }
+
+    async fn restore_checkpoint(
+        &self,
+        checkpoint_id: CheckpointId,
+        policy: ConflictPolicy,
+        rewind_conversation: bool,
+    ) -> Result<RestoreReport, CheckpointError> {
+        let checkpoint = self.checkpoints.read()
+            .get(&checkpoint_id)
+            .cloned()
+            .ok_or(CheckpointError::NotFound(checkpoint_id))?;
+
+        info!(
+            checkpoint_id = %checkpoint_id,
+            name = ?checkpoint.name,
+            "Restoring checkpoint"
+        );
+
+        // Restore file state
+        let mut report = self.restore_files(checkpoint_id, &checkpoint.data, policy, None).await?;
+
+        // Update current
+        self.set_current(checkpoint_id).await?;
+
+        // Rewind the conversation and agent states; the files are already
+        // restored, so failures here are reported rather than returned
+        let provider = self.conversation_provider().filter(|_| rewind_conversation);
+        if let (Some(provider), Some(snapshot)) = (provider, &checkpoint.data.conversation_snapshot) {
+            match provider.restore(snapshot) {
+                Ok(()) => report.conversation_restored = true,
+                Err(e) => {
+                    warn!(checkpoint_id = %checkpoint_id, error = %e, "Failed to restore conversation");
+                    report.conversation_error = Some(e.to_string());
+                }
+            }
+        }
+
+        let outcome = self.agent_states.restore_all(&checkpoint.data.agent_states);
+        report.agent_states_restored = outcome.restored;
+        report.agent_state_errors = outcome.failed;
+        report.agent_states_missing = outcome.missing.into_iter()
+            .map(|name| {
+                let reason = checkpoint.data.agent_state_errors.get(&name)
+                    .map(|e| format!("snapshot failed: {e}"))
+                    .unwrap_or_else(|| "no state saved in checkpoint".to_string());
+                (name, reason)
+            })
+            .collect();
+
+        Ok(report)
+    }
+
+    fn conversation_provider(&self) -> Option<Arc<dyn ConversationProvider>> {
+        self.conversation.read().clone()
+    }
+
+    /// The checkpoint itself followed by its ancestors, nearest first.
+    /// Stops at the first pruned ancestor.
+    fn ancestors(&self, id: CheckpointId) -> Vec<CheckpointId> {
+        let checkpoints = self.checkpoints.read();
+        let mut ancestors = Vec::new();
+        let mut next = Some(id);
+        
+        while let Some(id) = next.filter(|id| checkpoints.contains_key(id)) {
+            ancestors.push(id);
+            next = checkpoints[&id].parent;
+        }
+        
+        ancestors
+    }
+
+    fn undo_target(&self) -> Option<CheckpointId> {
+        let current = (*self.current.read())?;
+        let checkpoints = self.checkpoints.read();
+        
+        checkpoints.get(&current)?
+            .parent
+            .filter(|parent| checkpoints.contains_key(parent))
+    }
+
+    fn redo_target(&self) -> Option<CheckpointId> {
+        let current = (*self.current.read())?;
+        let tip = (*self.tip.read())?;
+        
+        // The ancestor of the tip whose parent is the current checkpoint
+        let path = self.ancestors(tip);
+        let idx = path.iter().position(|&id| id == current)?;
+        idx.checked_sub(1).map(|child| path[child])
+    }
+
+    async fn set_tip(&self, id: CheckpointId) -> Result<(), CheckpointError> {
+        *self.tip.write() = Some(id);
+        self.storage.save_tip(Some(&id)).await
+    }
+
+    async fn set_current(&self, id: CheckpointId) -> Result<(), CheckpointError> {
+        *self.current.write() = Some(id);
+        self.storage.save_head(Some(&id)).await
+    }
+
+    /// Persist a checkpoint, storing its conversation as a delta against
+    /// its parent's when they share messages
+    async fn store_checkpoint(&self, checkpoint: &Checkpoint) -> Result<u64, CheckpointError> {
+        let Some(delta) = self.conversation_delta(checkpoint) else {
+            return self.storage.save(checkpoint).await;
+        };
+
+        let mut stored = checkpoint.clone();
+        stored.data.conversation_snapshot = None;
+        stored.data.conversation_delta = Some(delta);
+        self.storage.save(&stored).await
+    }
+
+    fn conversation_delta(&self, checkpoint: &Checkpoint) -> Option<ConversationDelta> {
+        let snapshot = checkpoint.data.conversation_snapshot.as_ref()?;
+        let checkpoints = self.checkpoints.read();
+        let parent = checkpoints.get(&checkpoint.parent?)?;
+        snapshot.delta_from(parent.data.conversation_snapshot.as_ref()?)
+    }
+
+    /// Replace stored conversation deltas with full snapshots, rebuilding
+    /// each chain from the nearest ancestor that has one
+    fn expand_conversations(checkpoints: &mut HashMap<CheckpointId, Checkpoint>) {
+        let ids: Vec<CheckpointId> = checkpoints.keys().copied().collect();
+        for id in ids {
+            let mut chain = Vec::new();
+            let mut next = Some(id);
+            while let Some(checkpoint) = next.and_then(|id| checkpoints.get(&id)) {
+                if checkpoint.data.conversation_delta.is_none() {
+                    break;
+                }
+                chain.push(checkpoint.id);
+                next = checkpoint.parent;
+            }
+
+            let mut base = next
+                .and_then(|id| checkpoints.get(&id))
+                .and_then(|c| c.data.conversation_snapshot.clone());
+            for id in chain.into_iter().rev() {
+                let Some(checkpoint) = checkpoints.get_mut(&id) else {
+                    continue;
+                };
+                let snapshot = checkpoint.data.conversation_delta.take()
+                    .zip(base.as_ref())
+                    .and_then(|(delta, parent)| delta.apply(parent));
+                if snapshot.is_none() {
+                    warn!(checkpoint_id = %id, "Parent conversation missing, dropping conversation snapshot");
+                }
+                checkpoint.data.conversation_snapshot = snapshot.clone();
+                base = snapshot;
+            }
+        }
+    }
+
+    /// Restore files all-or-nothing: either every file reaches the
+    /// checkpoint state or the workspace is left exactly as it was.
+    ///
+    /// Files modified outside of hutch are handled according to `policy`.
+    /// With `only`, files not matching it are neither restored nor have
+    /// their tracked state changed.
+    async fn restore_files(
+        &self,
+        checkpoint_id: CheckpointId,
+        data: &CheckpointData,
+        policy: ConflictPolicy,
+        only: Option<&PathMatcher>,
+    ) -> Result<RestoreReport, CheckpointError> {
+        let mut states = self.load_file_states(data).await?;
+        let mut removals = self.files_to_remove(data);
+        states.retain(|path, _| self.should_track(path));
+        removals.retain(|path| self.should_track(path));
+        if let Some(matcher) = only {
+            states.retain(|path, _| matcher.matches_or_ancestor(path));
+            removals.retain(|path| matcher.matches_or_ancestor(path));
+        }
+        let touched: HashSet<PathBuf> = states.keys().chain(&removals).cloned().collect();
+
+        let mut writes: Vec<_> = states.iter().collect();
+        writes.sort_by(|a, b| a.0.cmp(b.0));
+        let mut sorted_removals: Vec<_> = removals.iter().collect();
+        sorted_removals.sort();
+        
+        let mut transaction = RestoreTransaction::new();
+        for (path, content) in writes {
+            transaction.write(path.clone(), content.as_bytes().to_vec()).await?;
+        }
+        for path in sorted_removals {
+            transaction.remove(path.clone()).await?;
+        }
+
+        let conflicts: Vec<(PathBuf, Option<Vec<u8>>)> = transaction.pending()
+            .filter(|(path, on_disk)| self.is_externally_modified(path, *on_disk))
+            .map(|(path, on_disk)| (path.clone(), on_disk.map(<[u8]>::to_vec)))
+            .collect();
+        let conflict_paths: Vec<PathBuf> = conflicts.iter()
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let mut merged = Vec::new();
+        let skipped = match policy {
+            ConflictPolicy::Abort if !conflicts.is_empty() => {
+                warn!(files = ?conflict_paths, "Restore aborted by external modifications");
+                return Err(CheckpointError::Conflict(conflict_paths));
+            }
+            ConflictPolicy::SkipConflicting => {
+                transaction.exclude(&conflict_paths);
+                conflict_paths
+            }
+            ConflictPolicy::Merge => {
+                transaction.exclude(&conflict_paths);
+
+                let mut skipped = Vec::new();
+                for (path, on_disk) in conflicts {
+                    let base = self.file_tracker.read().get_state(&path).cloned();
+                    let texts = (
+                        base.as_ref().and_then(FileContent::as_text),
+                        states.get(&path).and_then(FileContent::as_text),
+                        on_disk.as_deref().and_then(|d| std::str::from_utf8(d).ok()),
+                    );
+
+                    // Deletions on either side and binary files cannot be line-merged
+                    let (Some(base), Some(ours), Some(theirs)) = texts else {
+                        skipped.push(path);
+                        continue;
+                    };
+
+                    let result = merge3(base, ours, theirs);
+                    transaction.write(path.clone(), result.text.as_bytes().to_vec()).await?;
+                    merged.push(FileMerge { path: path.clone(), conflicts: result.conflicts });
+                    states.insert(path, FileContent::from(result.text));
+                }
+                skipped
+            }
+            _ => Vec::new(),
+        };
+
+        let changed = transaction.apply().await
+            .map_err(CheckpointError::RestoreFailed)?;
+        debug!(
+            files = changed.len(),
+            skipped = skipped.len(),
+            merged = merged.len(),
+            "Restored files"
+        );
+        
+        // Update file tracker, keeping what it last knew about skipped files
+        let mut file_tracker = self.file_tracker.write();
+        for path in &skipped {
+            states.remove(path);
+            removals.remove(path);
+            if let Some(recorded) = file_tracker.get_state(path) {
+                states.insert(path.clone(), recorded.clone());
+            } else if file_tracker.is_deleted(path) {
+                removals.insert(path.clone());
+            }
+        }
+        if only.is_some() {
+            file_tracker.reset_paths(&touched, &states, &removals);
+        } else {
+            file_tracker.reset_to(&states, &removals);
+        }
+        
+        Ok(RestoreReport {
+            checkpoint_id,
+            changed,
+            skipped,
+            merged,
+            conversation_restored: false,
+            conversation_error: None,
+            agent_states_restored: Vec::new(),
+            agent_state_errors: Vec::new(),
+            agent_states_missing: Vec::new(),
+        })
+    }
+
+    /// Files that must not exist once `data` is restored: those deleted at
+    /// checkpoint time, plus tracked files absent from the checkpoint that
+    /// were created by tracked changes afterwards
+    fn files_to_remove(&self, data: &CheckpointData) -> HashSet<PathBuf> {
+        let file_tracker = self.file_tracker.read();
+        let mut removals = data.deleted_files.clone();
+        
+        for path in file_tracker.current_states().keys() {
+            let created = file_tracker.was_created(path) || data.created_files.contains(path);
+            if created && !data.file_states.contains_key(path) {
+                removals.insert(path.clone());
+            }
+        }
+        
+        removals
+    }
+
+    /// Whether a file on disk differs from the last state hutch recorded.
+    /// Files hutch knows nothing about are never considered modified.
+    fn is_externally_modified(&self, path: &PathBuf, on_disk: Option<&[u8]>) -> bool {
+        let file_tracker = self.file_tracker.read();
+        
+        match file_tracker.get_state(path) {
+            Some(recorded) => on_disk != Some(recorded.as_bytes()),
+            None => file_tracker.is_deleted(path) && on_disk.is_some(),
+        }
+    }
+
+    /// File contents to restore for a checkpoint: its recorded states,
+    /// plus the original content of files first changed after it was taken
+    async fn load_file_states(&self, data: &CheckpointData) -> Result<HashMap<PathBuf, FileContent>, CheckpointError> {
+        let mut states = self.unchanged_originals(data);
+        for (path, blob) in &data.file_states {
+            states.insert(path.clone(), FileContent::from_bytes(self.blobs.get(&blob.hash).await?));
+        }
+        Ok(states)
+    }
+
+    /// Original contents of files the checkpoint knows nothing about,
+    /// which therefore still had that content when it was taken
+    fn unchanged_originals(&self, data: &CheckpointData) -> HashMap<PathBuf, FileContent> {
+        self.file_tracker.read()
+            .original_states()
+            .iter()
+            .filter(|(path, _)| {
+                !data.file_states.contains_key(*path) && !data.deleted_files.contains(*path)
+            })
+            .map(|(path, content)| (path.clone(), content.clone()))
+            .collect()
+    }
+
+    async fn prune_checkpoints(&self) -> Result<(), CheckpointError> {
+        let mut pruned = Vec::new();
+
+        {
+            let current = *self.current.read();
+            let tip = *self.tip.read();
+            let mut order = self.order.write();
+            let mut checkpoints = self.checkpoints.write();
+            
+            while order.len() > self.config.max_checkpoints {
+                // Named checkpoints stay listed; prune the oldest unnamed one,
+                // never the current checkpoint or branch tip
+                let oldest = order.iter().position(|id| {
+                    Some(*id) != current
+                        && Some(*id) != tip
+                        && checkpoints.get(id).is_none_or(|c| c.name.is_none())
+                });
+                let Some(idx) = oldest else {
+                    break;
+                };
+                
+                let oldest_id = order.remove(idx);
+                if let Some(checkpoint) = checkpoints.remove(&oldest_id) {
+                    pruned.push(checkpoint);
+                }
+            }
+        }
+
+        // Children may store their conversation as a delta against a pruned
+        // checkpoint; rewrite them in full before it goes away
+        let pruned_ids: HashSet<CheckpointId> = pruned.iter().map(|c| c.id).collect();
+        let orphans: Vec<Checkpoint> = self.checkpoints.read()
+            .values()
+            .filter(|c| c.parent.is_some_and(|p| pruned_ids.contains(&p)))
+            .filter(|c| c.data.conversation_snapshot.is_some())
+            .cloned()
+            .collect();
+        for checkpoint in orphans {
+            let size = self.store_checkpoint(&checkpoint).await?;
+            self.sizes.write().insert(checkpoint.id, size);
+        }
+
+        for checkpoint in pruned {
+            let id = checkpoint.id;
+            self.storage.delete(&id).await?;
+            self.sizes.write().remove(&id);
+            for blob in checkpoint.data.blob_refs() {
+                self.blobs.release(&blob.hash).await?;
+            }
+            debug!(checkpoint_id = %id, "Pruned old checkpoint");
+        }
+        
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::{tempdir, TempDir};
+    use chrono::DateTime;
+    use crate::checkpoint::{ConversationMessage, ConversationSnapshot, MessageRole};
+    use crate::error::HookError;
+
+    fn test_manager() -> (CheckpointManager, TempDir) {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            ..Default::default()
+        };
+        (CheckpointManager::new(config), dir)
+    }
+
+    #[tokio::test]
+    async fn test_manager_creation() {
+        let (manager, _dir) = test_manager();
+        assert_eq!(manager.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_save_checkpoint() {
+        let (manager, _dir) = test_manager();
+        
+        let id = manager.save(Some("test".to_string())).await.unwrap();
+        assert_eq!(manager.count(), 1);
+        assert_eq!(manager.current(), Some(id));
+    }
+
+    #[tokio::test]
+    async fn test_list_checkpoints() {
+        let (manager, _dir) = test_manager();
+        
+        manager.save(Some("first".to_string())).await.unwrap();
+        manager.save(Some("second".to_string())).await.unwrap();
+        
+        let list = manager.list();
+        assert_eq!(list.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_save_persists_to_storage() {
+        let (manager, dir) = test_manager();
+        
+        let id = manager.save(Some("persisted".to_string())).await.unwrap();
+        
+        let path = dir.path().join("checkpoints").join(format!("{}.json", id));
+        assert!(path.exists());
+
+        let on_disk = std::fs::metadata(&path).unwrap().len();
+        assert_eq!(manager.list()[0].size_bytes, on_disk);
+    }
+
+    #[tokio::test]
+    async fn test_prune_deletes_from_storage() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            max_checkpoints: 2,
+            ..Default::default()
+        };
+        let manager = CheckpointManager::new(config);
+        let task_id = TaskId::new();
+        
+        let first = manager.checkpoint_turn(task_id, 0).await.unwrap();
+        manager.checkpoint_turn(task_id, 1).await.unwrap();
+        manager.checkpoint_turn(task_id, 2).await.unwrap();
+        
+        assert_eq!(manager.count(), 2);
+        assert!(manager.get(&first).is_none());
+        assert!(!dir.path().join("checkpoints").join(format!("{}.json", first)).exists());
+    }
+
+    #[tokio::test]
+    async fn test_open_skips_corrupt_checkpoint() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            ..Default::default()
+        };
+        
+        let (first, second) = {
+            let manager = CheckpointManager::new(config.clone());
+            let first = manager.save(Some("first".into())).await.unwrap();
+            let second = manager.save(Some("second".into())).await.unwrap();
+            (first, second)
+        };
+        let path = dir.path().join("checkpoints").join(format!("{}.json", second));
+        let json = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, &json[..json.len() / 2]).unwrap();
+        
+        let manager = CheckpointManager::open(config).await.unwrap();
+        
+        assert_eq!(manager.count(), 1);
+        assert!(manager.get(&first).is_some());
+        assert_eq!(manager.unreadable_checkpoints(), &[second]);
+        assert_eq!(manager.current(), Some(first));
+    }
+
+    #[tokio::test]
+    async fn test_open_skips_missing_blobs() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            ..Default::default()
+        };
+        let (lost, kept) = (dir.path().join("lost.txt"), dir.path().join("kept.txt"));
+        
+        let id = {
+            let manager = CheckpointManager::new(config.clone());
+            manager.record_file_change(lost.clone(), Some("lost before".to_string()), "lost after".to_string());
+            manager.record_file_change(kept.clone(), None, "kept".to_string());
+            manager.save(None).await.unwrap()
+        };
+        let blobs = dir.path().join("checkpoints").join("blobs");
+        for content in ["lost before", "lost after"] {
+            let hash = BlobRef::for_content(content.as_bytes()).hash;
+            let (prefix, rest) = hash.as_str().split_at(2);
+            std::fs::remove_file(blobs.join(prefix).join(rest)).unwrap();
+        }
+        
+        let manager = CheckpointManager::open(config).await.unwrap();
+        
+        assert_eq!(manager.current(), Some(id));
+        assert_eq!(manager.file_state(&lost), None);
+        assert_eq!(manager.file_state(&kept), Some(FileContent::from("kept")));
+    }
+
+    #[tokio::test]
+    async fn test_open_empty_storage() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            ..Default::default()
+        };
+        
+        let manager = CheckpointManager::open(config).await.unwrap();
+        assert_eq!(manager.count(), 0);
+        assert_eq!(manager.current(), None);
+    }
+
+    #[tokio::test]
+    async fn test_open_rehydrates_checkpoints() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            ..Default::default()
+        };
+        let file = dir.path().join("work.txt");
+        let task_id = TaskId::new();
+        
+        let (first, second) = {
+            let manager = CheckpointManager::new(config.clone());
+            std::fs::write(&file, "one").unwrap();
+            manager.record_file_change(file.clone(), None, "one".to_string());
+            let first = manager.checkpoint_turn(task_id, 0).await.unwrap();
+            std::fs::write(&file, "two").unwrap();
+            manager.record_file_change(file.clone(), Some("one".to_string()), "two".to_string());
+            let second = manager.checkpoint_turn(task_id, 1).await.unwrap();
+            manager.undo().await.unwrap();
+            (first, second)
+        };
+        
+        let manager = CheckpointManager::open(config).await.unwrap();
+        
+        let ids: Vec<_> = manager.list().into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![first, second]);
+        assert_eq!(manager.current(), Some(first));
+        assert_eq!(manager.turn_tracker.read().checkpoint_for_task_turn(task_id, 1), Some(second));
+        assert_eq!(
+            manager.file_tracker.read().get_state(&file),
+            Some(&FileContent::from("one"))
+        );
+        
+        // Undo history keeps working across the reopen
+        assert!(matches!(manager.undo().await, Err(CheckpointError::NothingToUndo)));
+        assert_eq!(manager.redo().await.unwrap(), second);
+    }
+
+    #[tokio::test]
+    async fn test_undo_redo() {
+        let (manager, _dir) = test_manager();
+        let task_id = TaskId::new();
+        
+        let first = manager.checkpoint_turn(task_id, 0).await.unwrap();
+        let second = manager.checkpoint_turn(task_id, 1).await.unwrap();
+        
+        assert!(manager.can_undo());
+        assert!(!manager.can_redo());
+        assert!(matches!(manager.redo().await, Err(CheckpointError::NothingToRedo)));
+        
+        assert_eq!(manager.undo().await.unwrap(), first);
+        assert!(!manager.can_undo());
+        assert!(manager.can_redo());
+        
+        assert_eq!(manager.redo().await.unwrap(), second);
+        assert_eq!(manager.current(), Some(second));
+        assert!(!manager.can_redo());
+    }
+
+    #[tokio::test]
+    async fn test_save_after_undo_starts_branch() {
+        let (manager, _dir) = test_manager();
+        let task_id = TaskId::new();
+        
+        let root = manager.checkpoint_turn(task_id, 0).await.unwrap();
+        let attempt_a = manager.checkpoint_turn(task_id, 1).await.unwrap();
+        
+        manager.undo().await.unwrap();
+        let attempt_b = manager.checkpoint_turn(task_id, 1).await.unwrap();
+        
+        // Both attempts remain reachable
+        assert_eq!(manager.count(), 3);
+        assert_eq!(manager.get(&attempt_b).unwrap().parent, Some(root));
+        assert_eq!(manager.children(&root), vec![attempt_a, attempt_b]);
+        
+        let tips: Vec<_> = manager.branches().into_iter().map(|m| m.id).collect();
+        assert_eq!(tips, vec![attempt_a, attempt_b]);
+        
+        // Redo follows the active branch
+        assert!(!manager.can_redo());
+        manager.undo().await.unwrap();
+        assert_eq!(manager.redo().await.unwrap(), attempt_b);
+        
+        // Switching branches changes what undo/redo walk along
+        manager.switch_branch(attempt_a).await.unwrap();
+        assert_eq!(manager.tip(), Some(attempt_a));
+        assert_eq!(manager.undo().await.unwrap(), root);
+        assert_eq!(manager.redo().await.unwrap(), attempt_a);
+    }
+
+    #[tokio::test]
+    async fn test_restore_ancestor_keeps_branch_redoable() {
+        let (manager, _dir) = test_manager();
+        let task_id = TaskId::new();
+        
+        let root = manager.checkpoint_turn(task_id, 0).await.unwrap();
+        let middle = manager.checkpoint_turn(task_id, 1).await.unwrap();
+        let tip = manager.checkpoint_turn(task_id, 2).await.unwrap();
+        
+        manager.restore(root).await.unwrap();
+        assert_eq!(manager.tip(), Some(tip));
+        assert_eq!(manager.redo().await.unwrap(), middle);
+    }
+
+    #[tokio::test]
+    async fn test_path_and_common_ancestor() {
+        let (manager, _dir) = test_manager();
+        let task_id = TaskId::new();
+        
+        let root = manager.checkpoint_turn(task_id, 0).await.unwrap();
+        let fork = manager.checkpoint_turn(task_id, 1).await.unwrap();
+        let left = manager.checkpoint_turn(task_id, 2).await.unwrap();
+        manager.undo().await.unwrap();
+        let right = manager.checkpoint_turn(task_id, 2).await.unwrap();
+        
+        assert_eq!(manager.path_to(&left).unwrap(), vec![root, fork, left]);
+        assert_eq!(manager.path_to(&right).unwrap(), vec![root, fork, right]);
+        assert_eq!(manager.common_ancestor(&left, &right).unwrap(), Some(fork));
+        assert_eq!(manager.common_ancestor(&left, &root).unwrap(), Some(root));
+        
+        let missing = CheckpointId::new();
+        assert!(matches!(manager.path_to(&missing), Err(CheckpointError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_checkpoints_share_blobs() {
+        let (manager, dir) = test_manager();
+        let task_id = TaskId::new();
+        let file = dir.path().join("lib.rs");
+        
+        manager.record_file_change(file.clone(), None, "pub fn a() {}".to_string());
+        let first = manager.checkpoint_turn(task_id, 0).await.unwrap();
+        let second = manager.checkpoint_turn(task_id, 1).await.unwrap();
+        
+        let blob_a = manager.get(&first).unwrap().data.file_states[&file].clone();
+        let blob_b = manager.get(&second).unwrap().data.file_states[&file].clone();
+        assert_eq!(blob_a, blob_b);
+        // Referenced by both snapshots and by the first checkpoint's change journal
+        assert_eq!(manager.blobs.ref_count(&blob_a.hash), 3);
+        
+        // The checkpoint JSON references the digest, not the content
+        let json = std::fs::read_to_string(dir.path().join("checkpoints").join(format!("{}.json", first))).unwrap();
+        assert!(json.contains(blob_a.hash.as_str()));
+        assert!(!json.contains("pub fn a() {}"));
+    }
+
+    #[tokio::test]
+    async fn test_prune_reclaims_unreferenced_blobs() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            max_checkpoints: 1,
+            ..Default::default()
+        };
+        let manager = CheckpointManager::new(config);
+        let task_id = TaskId::new();
+        let file = dir.path().join("lib.rs");
+        
+        manager.record_file_change(file.clone(), None, "old".to_string());
+        let first = manager.checkpoint_turn(task_id, 0).await.unwrap();
+        let old_blob = manager.get(&first).unwrap().data.file_states[&file].clone();
+        
+        manager.record_file_change(file.clone(), Some("old".to_string()), "new".to_string());
+        let second = manager.checkpoint_turn(task_id, 1).await.unwrap();
+        let new_blob = manager.get(&second).unwrap().data.file_states[&file].clone();
+        
+        // The second checkpoint's change journal still holds the old content
+        assert!(manager.get(&first).is_none());
+        assert!(manager.blobs.exists(&old_blob.hash));
+        
+        manager.checkpoint_turn(task_id, 2).await.unwrap();
+        
+        assert!(manager.get(&second).is_none());
+        assert!(!manager.blobs.exists(&old_blob.hash));
+        assert!(manager.blobs.exists(&new_blob.hash));
+    }
+
+    #[tokio::test]
+    async fn test_prune_keeps_named_checkpoints_listed() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            max_checkpoints: 2,
+            ..Default::default()
+        };
+        let manager = CheckpointManager::new(config);
+        let task_id = TaskId::new();
+        
+        let baseline = manager.save(Some(BASELINE_NAME.to_string())).await.unwrap();
+        let first = manager.checkpoint_turn(task_id, 0).await.unwrap();
+        let second = manager.checkpoint_turn(task_id, 1).await.unwrap();
+        let third = manager.checkpoint_turn(task_id, 2).await.unwrap();
+        
+        let ids: Vec<_> = manager.list().into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec![baseline, third]);
+        assert!(manager.get(&first).is_none() && manager.get(&second).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_restore_reads_blobs() {
+        let (manager, dir) = test_manager();
+        let file = dir.path().join("notes.txt");
+        
+        std::fs::write(&file, "before").unwrap();
+        manager.record_file_change(file.clone(), None, "before".to_string());
+        let id = manager.save(Some("before".to_string())).await.unwrap();
+        std::fs::write(&file, "after").unwrap();
+        manager.record_file_change(file.clone(), Some("before".to_string()), "after".to_string());
+        
+        manager.restore(id).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "before");
+    }
+
+    #[tokio::test]
+    async fn test_restore_binary_file() {
+        let (manager, dir) = test_manager();
+        let file = dir.path().join("image.bin");
+        let original = vec![0x00, 0x9f, 0x92, 0x96, 0xff];
+        
+        std::fs::write(&file, &original).unwrap();
+        manager.record_binary_file_change(file.clone(), None, original.clone());
+        let id = manager.save(None).await.unwrap();
+        std::fs::write(&file, [0x01]).unwrap();
+        manager.record_binary_file_change(file.clone(), Some(original.clone()), vec![0x01]);
+        
+        manager.restore(id).await.unwrap();
+        assert_eq!(std::fs::read(&file).unwrap(), original);
+    }
+
+    #[tokio::test]
+    async fn test_restore_removes_files_created_later() {
+        let (manager, dir) = test_manager();
+        let existing = dir.path().join("existing.txt");
+        let created = dir.path().join("created.txt");
+        std::fs::write(&existing, "v1").unwrap();
+        
+        manager.record_file_change(existing.clone(), Some("v0".to_string()), "v1".to_string());
+        let before = manager.save(None).await.unwrap();
+        
+        std::fs::write(&created, "new").unwrap();
+        manager.record_file_change(created.clone(), None, "new".to_string());
+        manager.save(None).await.unwrap();
+        
+        manager.restore(before).await.unwrap();
+        assert!(!created.exists());
+        assert!(existing.exists());
+        
+        // Redo brings it back
+        manager.redo().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&created).unwrap(), "new");
+    }
+
+    #[tokio::test]
+    async fn test_restore_deleted_file() {
+        let (manager, dir) = test_manager();
+        let file = dir.path().join("doomed.txt");
+        std::fs::write(&file, "keep me").unwrap();
+        
+        manager.record_file_change(file.clone(), None, "keep me".to_string());
+        let before = manager.save(None).await.unwrap();
+        
+        std::fs::remove_file(&file).unwrap();
+        manager.record_file_deletion(file.clone(), None);
+        let after = manager.save(None).await.unwrap();
+        assert!(manager.get(&after).unwrap().data.deleted_files.contains(&file));
+        
+        manager.restore(before).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep me");
+        
+        manager.restore(after).await.unwrap();
+        assert!(!file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_restore_keeps_untracked_preexisting_files() {
+        let (manager, dir) = test_manager();
+        let file = dir.path().join("preexisting.txt");
+        
+        let before = manager.save(None).await.unwrap();
+        
+        // First seen after the checkpoint, but it existed already
+        std::fs::write(&file, "edited").unwrap();
+        manager.record_file_change(file.clone(), Some("original".to_string()), "edited".to_string());
+        
+        manager.restore(before).await.unwrap();
+        assert!(file.exists());
+    }
+
+    #[tokio::test]
+    async fn test_diff_between_checkpoints() {
+        let (manager, dir) = test_manager();
+        let lib = dir.path().join("lib.rs");
+        let added = dir.path().join("added.rs");
+        
+        manager.record_file_change(lib.clone(), None, "fn a() {}\nfn b() {}\n".to_string());
+        let from = manager.save(None).await.unwrap();
+        
+        manager.record_file_change(
+            lib.clone(),
+            Some("fn a() {}\nfn b() {}\n".to_string()),
+            "fn a() {}\nfn c() {}\n".to_string(),
+        );
+        manager.record_file_change(added.clone(), None, "mod x;\n".to_string());
+        let to = manager.save(None).await.unwrap();
+        
+        let diff = manager.diff(&from, &to).await.unwrap();
+        
+        assert_eq!(diff.files.len(), 2);
+        assert_eq!(diff.insertions(), 2);
+        assert_eq!(diff.deletions(), 1);
+        
+        let unified = diff.to_unified();
+        assert!(unified.contains("-fn b() {}"));
+        assert!(unified.contains("+fn c() {}"));
+        assert!(unified.contains("--- /dev/null"));
+        
+        // Same checkpoint diffs to nothing
+        assert!(manager.diff(&to, &to).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_plan_restore() {
+        let (manager, dir) = test_manager();
+        let edited = dir.path().join("edited.txt");
+        let removed = dir.path().join("removed.txt");
+        let created = dir.path().join("created.txt");
+        let untouched = dir.path().join("untouched.txt");
+        
+        std::fs::write(&edited, "v1").unwrap();
+        std::fs::write(&removed, "gone soon").unwrap();
+        std::fs::write(&untouched, "same").unwrap();
+        manager.record_file_change(edited.clone(), Some("v0".to_string()), "v1".to_string());
+        manager.record_file_change(removed.clone(), Some("x".to_string()), "gone soon".to_string());
+        manager.record_file_change(untouched.clone(), Some("x".to_string()), "same".to_string());
+        let target = manager.save(None).await.unwrap();
+        
+        std::fs::write(&edited, "v2").unwrap();
+        manager.record_file_change(edited.clone(), Some("v1".to_string()), "v2".to_string());
+        std::fs::remove_file(&removed).unwrap();
+        manager.record_file_deletion(removed.clone(), None);
+        std::fs::write(&created, "brand new").unwrap();
+        manager.record_file_change(created.clone(), None, "brand new".to_string());
+        
+        let plan = manager.plan_restore(target).await.unwrap();
+        
+        assert_eq!(plan.overwrite, vec![edited.clone()]);
+        assert_eq!(plan.create, vec![removed.clone()]);
+        assert_eq!(plan.delete, vec![created.clone()]);
+        assert_eq!(plan.bytes_to_write, 2 + 9);
+        assert_eq!(plan.bytes_to_delete, 9);
+        assert!(!plan.has_external_modifications());
+        
+        // Nothing was touched
+        assert_eq!(std::fs::read_to_string(&edited).unwrap(), "v2");
+        assert!(created.exists());
+    }
+
+    #[tokio::test]
+    async fn test_plan_restore_detects_external_modifications() {
+        let (manager, dir) = test_manager();
+        let file = dir.path().join("shared.txt");
+        
+        std::fs::write(&file, "agent v1").unwrap();
+        manager.record_file_change(file.clone(), None, "agent v1".to_string());
+        let target = manager.save(None).await.unwrap();
+        
+        std::fs::write(&file, "agent v2").unwrap();
+        manager.record_file_change(file.clone(), Some("agent v1".to_string()), "agent v2".to_string());
+        
+        // A human edits the file behind hutch's back
+        std::fs::write(&file, "human edit").unwrap();
+        
+        let plan = manager.plan_restore(target).await.unwrap();
+        assert_eq!(plan.externally_modified, vec![file]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_restore_leaves_workspace_untouched() {
+        let (manager, dir) = test_manager();
+        let good = dir.path().join("a.txt");
+        let blocked = dir.path().join("sub/b.txt");
+        
+        manager.record_file_change(good.clone(), None, "checkpoint a".to_string());
+        manager.record_file_change(blocked.clone(), None, "checkpoint b".to_string());
+        let target = manager.save(None).await.unwrap();
+        
+        std::fs::write(&good, "current a").unwrap();
+        manager.record_file_change(good.clone(), Some("checkpoint a".to_string()), "current a".to_string());
+        // Something replaces the directory with a file, so b.txt cannot be written
+        std::fs::write(dir.path().join("sub"), "not a dir").unwrap();
+        
+        let err = manager.restore_with_policy(target, ConflictPolicy::Overwrite).await.unwrap_err();
+        
+        let CheckpointError::RestoreFailed(failure) = err else {
+            panic!("expected RestoreFailed, got {:?}", err);
+        };
+        assert_eq!(failure.failed[0].0, blocked);
+        assert_eq!(std::fs::read_to_string(&good).unwrap(), "current a");
+        
+        // Tracker and current pointer are unchanged
+        assert_eq!(manager.file_tracker.read().get_state(&good).unwrap(), "current a");
+        assert_ne!(manager.current(), None);
+    }
+
+    /// Checkpoint a file, advance it, then have a "human" edit it on disk
+    async fn conflicted_manager() -> (CheckpointManager, TempDir, PathBuf, PathBuf, CheckpointId) {
+        let (manager, dir) = test_manager();
+        let edited = dir.path().join("edited.txt");
+        let clean = dir.path().join("clean.txt");
+        
+        for (path, content) in [(&edited, "v1"), (&clean, "v1")] {
+            std::fs::write(path, content).unwrap();
+            manager.record_file_change(path.clone(), None, content.to_string());
+        }
+        let target = manager.save(None).await.unwrap();
+        
+        for path in [&edited, &clean] {
+            std::fs::write(path, "v2").unwrap();
+            manager.record_file_change(path.clone(), Some("v1".to_string()), "v2".to_string());
+        }
+        std::fs::write(&edited, "human edit").unwrap();
+        
+        (manager, dir, edited, clean, target)
+    }
+
+    #[tokio::test]
+    async fn test_restore_conflict_aborts_by_default() {
+        let (manager, _dir, edited, clean, target) = conflicted_manager().await;
+        let current = manager.current();
+        
+        let err = manager.restore(target).await.unwrap_err();
+        
+        let CheckpointError::Conflict(paths) = err else {
+            panic!("expected Conflict, got {:?}", err);
+        };
+        assert_eq!(paths, vec![edited.clone()]);
+        assert_eq!(std::fs::read_to_string(&edited).unwrap(), "human edit");
+        assert_eq!(std::fs::read_to_string(&clean).unwrap(), "v2");
+        assert_eq!(manager.current(), current);
+    }
+
+    #[tokio::test]
+    async fn test_restore_conflict_overwrite() {
+        let (manager, _dir, edited, clean, target) = conflicted_manager().await;
+        
+        let report = manager.restore_with_policy(target, ConflictPolicy::Overwrite).await.unwrap();
+        
+        assert_eq!(report.changed.len(), 2);
+        assert!(!report.has_skipped());
+        assert_eq!(std::fs::read_to_string(&edited).unwrap(), "v1");
+        assert_eq!(std::fs::read_to_string(&clean).unwrap(), "v1");
+    }
+
+    #[tokio::test]
+    async fn test_restore_conflict_skip() {
+        let (manager, _dir, edited, clean, target) = conflicted_manager().await;
+        
+        let report = manager.restore_with_policy(target, ConflictPolicy::SkipConflicting).await.unwrap();
+        
+        assert_eq!(report.changed, vec![clean.clone()]);
+        assert_eq!(report.skipped, vec![edited.clone()]);
+        assert_eq!(std::fs::read_to_string(&edited).unwrap(), "human edit");
+        assert_eq!(std::fs::read_to_string(&clean).unwrap(), "v1");
+        
+        // The skipped file is still flagged as externally modified
+        let plan = manager.plan_restore(target).await.unwrap();
+        assert_eq!(plan.externally_modified, vec![edited]);
+    }
+
+    #[tokio::test]
+    async fn test_restore_merge_keeps_unrelated_edits() {
+        let (manager, dir) = test_manager();
+        let file = dir.path().join("config.toml");
+        let v1 = "name = \"demo\"\nversion = 1\n\n[deps]\nfoo = 1\n";
+        let v2 = "name = \"demo\"\nversion = 2\n\n[deps]\nfoo = 1\n";
+        
+        std::fs::write(&file, v1).unwrap();
+        manager.record_file_change(file.clone(), None, v1.to_string());
+        let target = manager.save(None).await.unwrap();
+        
+        std::fs::write(&file, v2).unwrap();
+        manager.record_file_change(file.clone(), Some(v1.to_string()), v2.to_string());
+        
+        // The user adds a dependency by hand
+        std::fs::write(&file, format!("{}bar = 2\n", v2)).unwrap();
+        
+        let report = manager.restore_with_policy(target, ConflictPolicy::Merge).await.unwrap();
+        
+        assert_eq!(report.merged.len(), 1);
+        assert!(report.merged[0].is_clean());
+        assert_eq!(
+            std::fs::read_to_string(&file).unwrap(),
+            format!("{}bar = 2\n", v1),
+        );
+    }
+
+    #[tokio::test]
+    async fn test_restore_merge_writes_conflict_markers() {
+        let (manager, _dir, edited, clean, target) = conflicted_manager().await;
+        
+        let report = manager.restore_with_policy(target, ConflictPolicy::Merge).await.unwrap();
+        
+        assert_eq!(report.merged.len(), 1);
+        assert_eq!(report.merged[0].path, edited);
+        assert_eq!(report.merged[0].conflicts, 1);
+        assert_eq!(std::fs::read_to_string(&clean).unwrap(), "v1");
+        
+        let merged = std::fs::read_to_string(&edited).unwrap();
+        assert!(merged.contains("<<<<<<< checkpoint\nv1\n=======\nhuman edit\n>>>>>>> working copy"));
+    }
+
+    // === Revert Turn Tests ===
+
+    /// Three turns: 0 writes the file, 1 edits line 2 and adds `added.txt`,
+    /// 2 edits line 5
+    async fn three_turn_manager() -> (CheckpointManager, TempDir, TaskId, PathBuf, PathBuf) {
+        let (manager, dir) = test_manager();
+        let task_id = TaskId::new();
+        let file = dir.path().join("lines.txt");
+        let added = dir.path().join("added.txt");
+        
+        let v0 = "1\n2\n3\n4\n5\n";
+        let v1 = "1\ntwo\n3\n4\n5\n";
+        let v2 = "1\ntwo\n3\n4\nfive\n";
+        
+        std::fs::write(&file, v0).unwrap();
+        manager.record_file_change(file.clone(), None, v0.to_string());
+        manager.checkpoint_turn(task_id, 0).await.unwrap();
+        
+        std::fs::write(&file, v1).unwrap();
+        manager.record_file_change(file.clone(), Some(v0.to_string()), v1.to_string());
+        std::fs::write(&added, "new").unwrap();
+        manager.record_file_change(added.clone(), None, "new".to_string());
+        manager.checkpoint_turn(task_id, 1).await.unwrap();
+        
+        std::fs::write(&file, v2).unwrap();
+        manager.record_file_change(file.clone(), Some(v1.to_string()), v2.to_string());
+        manager.checkpoint_turn(task_id, 2).await.unwrap();
+        
+        (manager, dir, task_id, file, added)
+    }
+
+    #[tokio::test]
+    async fn test_revert_turn_keeps_later_turns() {
+        let (manager, _dir, task_id, file, added) = three_turn_manager().await;
+        let current = manager.current();
+        
+        let report = manager.revert_turn(task_id, 1).await.unwrap();
+        
+        assert!(!report.has_conflicts());
+        assert_eq!(report.changed.len(), 2);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "1\n2\n3\n4\nfive\n");
+        assert!(!added.exists());
+        
+        // Reverting edits the working state; history is untouched
+        assert_eq!(manager.current(), current);
+        assert!(manager.file_tracker.read().is_deleted(&added));
+        assert_eq!(
+            manager.file_tracker.read().get_state(&file),
+            Some(&FileContent::from("1\n2\n3\n4\nfive\n"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_revert_turn_reports_conflicts() {
+        let (manager, _dir, task_id, file, added) = three_turn_manager().await;
+        
+        // A later turn edits the same line and the created file
+        let v3 = "1\nTWO\n3\n4\nfive\n";
+        std::fs::write(&file, v3).unwrap();
+        manager.record_file_change(file.clone(), None, v3.to_string());
+        std::fs::write(&added, "newer").unwrap();
+        manager.record_file_change(added.clone(), Some("new".to_string()), "newer".to_string());
+        manager.checkpoint_turn(task_id, 3).await.unwrap();
+        
+        let report = manager.revert_turn(task_id, 1).await.unwrap();
+        
+        assert!(report.has_conflicts());
+        assert_eq!(report.skipped, vec![added.clone()]);
+        assert_eq!(report.merged.len(), 1);
+        assert_eq!(report.merged[0].conflicts, 1);
+        assert_eq!(std::fs::read_to_string(&added).unwrap(), "newer");
+        assert!(std::fs::read_to_string(&file).unwrap().contains("<<<<<<< checkpoint\n2\n=======\nTWO\n"));
+    }
+
+    #[tokio::test]
+    async fn test_revert_turn_after_parent_is_pruned() {
+        let dir = tempdir().unwrap();
+        let manager = CheckpointManager::new(CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            max_checkpoints: 2,
+            ..Default::default()
+        });
+        let task_id = TaskId::new();
+        let (file, other) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+        
+        std::fs::write(&file, "v0").unwrap();
+        manager.record_file_change(file.clone(), None, "v0".to_string());
+        let first = manager.checkpoint_turn(task_id, 0).await.unwrap();
+        
+        std::fs::write(&file, "v1").unwrap();
+        manager.record_file_change(file.clone(), Some("v0".to_string()), "v1".to_string());
+        manager.checkpoint_turn(task_id, 1).await.unwrap();
+        
+        std::fs::write(&other, "b").unwrap();
+        manager.record_file_change(other.clone(), None, "b".to_string());
+        manager.checkpoint_turn(task_id, 2).await.unwrap();
+        assert!(manager.get(&first).is_none());
+        
+        let report = manager.revert_turn(task_id, 1).await.unwrap();
+        
+        assert_eq!(report.changed, vec![file.clone()]);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "v0");
+        assert_eq!(std::fs::read_to_string(&other).unwrap(), "b");
+    }
+
+    #[tokio::test]
+    async fn test_revert_unknown_turn() {
+        let (manager, _dir, task_id, _file, _added) = three_turn_manager().await;
+        let other = TaskId::new();
+        
+        assert!(matches!(manager.revert_turn(task_id, 4).await, Err(CheckpointError::TurnNotFound(_, 4))));
+        assert!(matches!(
+            manager.revert_turn(other, 1).await,
+            Err(CheckpointError::TurnNotFound(task, 1)) if task == other
+        ));
+    }
+
+    // === Restore Paths Tests ===
+
+    fn test_manager_in_workspace() -> (CheckpointManager, TempDir) {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            workspace_root: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        (CheckpointManager::new(config), dir)
+    }
+
+    #[tokio::test]
+    async fn test_restore_paths_only_touches_matches() {
+        let (manager, dir) = test_manager_in_workspace();
+        let lib = dir.path().join("src").join("lib.rs");
+        let main = dir.path().join("src").join("main.rs");
+        let notes = dir.path().join("notes.md");
+        std::fs::create_dir_all(lib.parent().unwrap()).unwrap();
+        
+        for path in [&lib, &main] {
+            std::fs::write(path, "v1").unwrap();
+            manager.record_file_change(path.clone(), None, "v1".to_string());
+        }
+        let target = manager.save(None).await.unwrap();
+        
+        for path in [&lib, &main] {
+            std::fs::write(path, "v2").unwrap();
+            manager.record_file_change(path.clone(), Some("v1".to_string()), "v2".to_string());
+        }
+        std::fs::write(&notes, "created").unwrap();
+        manager.record_file_change(notes.clone(), None, "created".to_string());
+        let latest = manager.save(None).await.unwrap();
+        
+        let report = manager.restore_paths(target, ["src/lib.rs"]).await.unwrap();
+        
+        assert_eq!(report.changed, vec![lib.clone()]);
+        assert_eq!(std::fs::read_to_string(&lib).unwrap(), "v1");
+        assert_eq!(std::fs::read_to_string(&main).unwrap(), "v2");
+        assert!(notes.exists());
+        assert_eq!(manager.current(), Some(latest));
+        
+        let file_tracker = manager.file_tracker.read();
+        assert_eq!(file_tracker.get_state(&lib), Some(&FileContent::from("v1")));
+        assert_eq!(file_tracker.get_state(&main), Some(&FileContent::from("v2")));
+    }
+
+    #[tokio::test]
+    async fn test_restore_paths_glob_removes_created_files() {
+        let (manager, dir) = test_manager_in_workspace();
+        let kept = dir.path().join("kept.txt");
+        let created = dir.path().join("created.txt");
+        
+        std::fs::write(&kept, "v1").unwrap();
+        manager.record_file_change(kept.clone(), None, "v1".to_string());
+        let target = manager.save(None).await.unwrap();
+        
+        std::fs::write(&created, "new").unwrap();
+        manager.record_file_change(created.clone(), None, "new".to_string());
+        manager.save(None).await.unwrap();
+        
+        let report = manager.restore_paths(target, ["*.txt"]).await.unwrap();
+        
+        assert_eq!(report.changed, vec![created.clone()]);
+        assert!(!created.exists());
+        assert!(manager.file_tracker.read().is_deleted(&created));
+    }
+
+    #[tokio::test]
+    async fn test_restore_paths_invalid_pattern() {
+        let (manager, _dir) = test_manager_in_workspace();
+        let id = manager.save(None).await.unwrap();
+        
+        let result = manager.restore_paths(id, ["[oops"]).await;
+        assert!(matches!(result, Err(CheckpointError::InvalidPattern(_))));
+    }
+
+    #[tokio::test]
+    async fn test_restore_paths_resolves_against_workspace_root() {
+        let (manager, dir) = test_manager_in_workspace();
+        let top = dir.path().join("src").join("lib.rs");
+        let nested = dir.path().join("crates").join("core").join("src").join("lib.rs");
+        std::fs::create_dir_all(top.parent().unwrap()).unwrap();
+        std::fs::create_dir_all(nested.parent().unwrap()).unwrap();
+        
+        for path in [&top, &nested] {
+            std::fs::write(path, "v1").unwrap();
+            manager.record_file_change(path.clone(), None, "v1".to_string());
+        }
+        let target = manager.save(None).await.unwrap();
+        for path in [&top, &nested] {
+            std::fs::write(path, "v2").unwrap();
+            manager.record_file_change(path.clone(), Some("v1".to_string()), "v2".to_string());
+        }
+        manager.save(None).await.unwrap();
+        
+        let report = manager.restore_paths(target, ["src/lib.rs"]).await.unwrap();
+        assert_eq!(report.changed, vec![top.clone()]);
+        assert_eq!(std::fs::read_to_string(&nested).unwrap(), "v2");
+        
+        let report = manager.restore_paths(target, ["crates"]).await.unwrap();
+        assert_eq!(report.changed, vec![nested.clone()]);
+    }
+
+    #[tokio::test]
+    async fn test_restore_paths_relative_needs_workspace_root() {
+        let (manager, dir) = test_manager();
+        let id = manager.save(None).await.unwrap();
+        
+        let result = manager.restore_paths(id, ["src/lib.rs"]).await;
+        assert!(matches!(result, Err(CheckpointError::NoWorkspaceRoot(_))));
+        
+        let absolute = dir.path().join("src/lib.rs");
+        assert!(manager.restore_paths(id, [absolute.to_string_lossy()]).await.is_ok());
+    }
+
+    // === Workspace Baseline Tests ===
+
+    #[tokio::test]
+    async fn test_snapshot_workspace_restores_pre_session_state() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("project");
+        let config = CheckpointConfig {
+            storage_dir: root.join(".lair").join("checkpoints"),
+            ..Default::default()
+        };
+        let manager = CheckpointManager::new(config);
+        let readme = root.join("README.md");
+        let build = root.join("target").join("out.bin");
+        std::fs::create_dir_all(build.parent().unwrap()).unwrap();
+        std::fs::write(&readme, "original").unwrap();
+        std::fs::write(&build, "artifact").unwrap();
+        
+        let baseline = manager.snapshot_workspace(&root, ["target"]).await.unwrap();
+        
+        let data = manager.get(&baseline).unwrap().data;
+        assert_eq!(data.file_states.len(), 1);
+        assert!(data.file_states.contains_key(&readme));
+        
+        // The agent edits without passing the old content
+        std::fs::write(&readme, "edited").unwrap();
+        manager.record_file_change(readme.clone(), None, "edited".to_string());
+        let new_file = root.join("notes.txt");
+        std::fs::write(&new_file, "agent notes").unwrap();
+        manager.record_file_change(new_file.clone(), None, "agent notes".to_string());
+        manager.save(None).await.unwrap();
+        
+        manager.restore(baseline).await.unwrap();
+        
+        assert_eq!(std::fs::read_to_string(&readme).unwrap(), "original");
+        assert!(!new_file.exists());
+        assert!(build.exists());
+    }
+
+    // === Tracking Filter Tests ===
+
+    #[tokio::test]
+    async fn test_excluded_files_are_not_recorded_or_restored() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        std::fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        let manager = CheckpointManager::new(CheckpointConfig {
+            storage_dir: PathBuf::from(".lair/checkpoints"),
+            workspace_root: Some(root.clone()),
+            exclude: vec!["generated/**".to_string()],
+            ..Default::default()
+        });
+        
+        let source = root.join("main.rs");
+        let log = root.join("run.log");
+        let generated = root.join("generated").join("out.rs");
+        let stored = root.join(".lair").join("checkpoints").join("HEAD");
+        
+        assert!(manager.should_track(&source));
+        assert!(!manager.should_track(&log));
+        assert!(!manager.should_track(&generated));
+        assert!(!manager.should_track(&stored));
+        assert_eq!(manager.storage_dir(), root.join(".lair").join("checkpoints"));
+        
+        for path in [&source, &log] {
+            manager.record_file_change(path.clone(), None, "v1".to_string());
+        }
+        assert_eq!(manager.file_state(&source), Some(FileContent::from("v1")));
+        assert_eq!(manager.file_state(&log), None);
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_workspace_honors_ignore_files() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().to_path_buf();
+        let (manager, _storage) = test_manager();
+        std::fs::write(root.join(".gitignore"), "dist/\n").unwrap();
+        std::fs::create_dir_all(root.join("dist")).unwrap();
+        std::fs::create_dir_all(root.join("node_modules").join("pkg")).unwrap();
+        std::fs::write(root.join("dist").join("bundle.js"), "min").unwrap();
+        std::fs::write(root.join("node_modules").join("pkg").join("index.js"), "dep").unwrap();
+        std::fs::write(root.join("index.js"), "src").unwrap();
+        
+        let baseline = manager.snapshot_workspace(&root, [".gitignore"]).await.unwrap();
+        
+        let paths: Vec<_> = manager.get(&baseline).unwrap().data.file_states.into_keys().collect();
+        assert_eq!(paths, vec![root.join("index.js")]);
+    }
+
+    #[tokio::test]
+    async fn test_open_rejects_invalid_patterns() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().to_path_buf(),
+            include: vec!["src/[".to_string()],
+            ..Default::default()
+        };
+        
+        assert!(matches!(
+            CheckpointManager::open(config).await,
+            Err(CheckpointError::InvalidPattern(_))
+        ));
+    }
+
+    #[test]
+    fn test_try_new_rejects_invalid_patterns() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            exclude: vec!["src/[".to_string()],
+            ..Default::default()
+        };
+        
+        assert!(matches!(
+            CheckpointManager::try_new(config.clone()),
+            Err(CheckpointError::InvalidPattern(_))
+        ));
+        
+        let manager = CheckpointManager::new(config);
+        assert!(manager.should_track(&dir.path().join("src/lib.rs")));
+    }
+
+    #[test]
+    fn test_default_excludes_only_apply_below_workspace_root() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().join("target").join("proj");
+        let manager = CheckpointManager::new(CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            workspace_root: Some(root.clone()),
+            ..Default::default()
+        });
+        
+        assert!(manager.should_track(&root.join("src").join("lib.rs")));
+        assert!(!manager.should_track(&root.join("target").join("debug").join("app")));
+    }
+
+    #[test]
+    fn test_relative_storage_dir_is_never_tracked() {
+        let manager = CheckpointManager::new(CheckpointConfig::default());
+        let storage = std::env::current_dir().unwrap()
+            .canonicalize().unwrap()
+            .join(".lair")
+            .join("checkpoints");
+        
+        assert_eq!(manager.storage_dir(), storage);
+        assert!(!manager.should_track(&storage.join("blobs").join("ab")));
+    }
+
+    #[tokio::test]
+    async fn test_default_excludes_need_workspace_root() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("node_modules").join("pkg").join("index.js");
+        let manager = CheckpointManager::new(CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            ..Default::default()
+        });
+        
+        manager.record_file_change(file.clone(), None, "module.exports = {}".to_string());
+        let id = manager.save(None).await.unwrap();
+        
+        assert!(manager.get(&id).unwrap().data.file_states.contains_key(&file));
+    }
+
+    // === Original State and Change Journal Tests ===
+
+    #[tokio::test]
+    async fn test_restore_uses_original_content() {
+        let (manager, dir) = test_manager();
+        let file = dir.path().join("existing.txt");
+        std::fs::write(&file, "before the agent").unwrap();
+        
+        // Nothing is known about the file yet
+        let before = manager.save(None).await.unwrap();
+        
+        std::fs::write(&file, "agent edit").unwrap();
+        manager.record_file_change(file.clone(), Some("before the agent".to_string()), "agent edit".to_string());
+        manager.save(None).await.unwrap();
+        
+        let plan = manager.plan_restore(before).await.unwrap();
+        assert_eq!(plan.overwrite, vec![file.clone()]);
+        
+        let diff = manager.diff(&before, &manager.current().unwrap()).await.unwrap();
+        assert_eq!(diff.files[0].kind, crate::diff::FileChangeKind::Modified);
+        
+        manager.restore(before).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "before the agent");
+    }
+
+    #[tokio::test]
+    async fn test_failed_save_keeps_pending_changes() {
+        let (manager, dir) = test_manager();
+        let file = dir.path().join("a.txt");
+        manager.record_file_change(file.clone(), None, "one".to_string());
+        
+        // A file where the storage directory should be makes saving fail
+        std::fs::write(dir.path().join("checkpoints"), "in the way").unwrap();
+        assert!(matches!(manager.save(None).await, Err(CheckpointError::StorageError(_))));
+        assert_eq!(manager.pending_changes().len(), 1);
+        
+        std::fs::remove_file(dir.path().join("checkpoints")).unwrap();
+        let id = manager.save(None).await.unwrap();
+        assert_eq!(manager.journal(&id).unwrap().len(), 1);
+        assert!(manager.pending_changes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_journals_pending_changes() {
+        let (manager, dir) = test_manager();
+        let file = dir.path().join("a.txt");
+        
+        manager.record_file_change(file.clone(), None, "one".to_string());
+        manager.record_file_change(file.clone(), Some("one".to_string()), "two".to_string());
+        let first = manager.save(None).await.unwrap();
+        let second = manager.save(None).await.unwrap();
+        
+        let changes = manager.get(&first).unwrap().data.changes;
+        assert_eq!(changes.len(), 2);
+        assert!(changes[0].old_content.is_none());
+        assert_eq!(changes[1].old_content, Some(BlobRef::for_content(b"one")));
+        assert_eq!(changes[1].new_content, Some(BlobRef::for_content(b"two")));
+        
+        assert!(manager.get(&second).unwrap().data.changes.is_empty());
+        assert!(manager.file_tracker.read().pending_changes().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_open_rehydrates_originals() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            ..Default::default()
+        };
+        let file = dir.path().join("existing.txt");
+        std::fs::write(&file, "original").unwrap();
+        
+        let before = {
+            let manager = CheckpointManager::new(config.clone());
+            let before = manager.save(None).await.unwrap();
+            std::fs::write(&file, "edited").unwrap();
+            manager.record_file_change(file.clone(), Some("original".to_string()), "edited".to_string());
+            manager.save(None).await.unwrap();
+            before
+        };
+        
+        let manager = CheckpointManager::open(config).await.unwrap();
+        manager.restore(before).await.unwrap();
+        
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "original");
+    }
+
+    #[tokio::test]
+    async fn test_journal_records_tool_and_reason() {
+        let (manager, dir) = test_manager();
+        let file = dir.path().join("main.rs");
+        let other = dir.path().join("other.rs");
+        
+        manager.record_tool_change(
+            file.clone(),
+            None,
+            Some("fn main() {}\n".into()),
+            ChangeSource::tool("write_file").with_reason("scaffold binary"),
+        );
+        manager.record_file_change(other.clone(), None, "x".to_string());
+        assert_eq!(manager.pending_changes().len(), 2);
+        let first = manager.save(None).await.unwrap();
+        
+        manager.record_tool_change(
+            file.clone(),
+            Some("fn main() {}\n".into()),
+            Some("fn main() {\n    run();\n}\n".into()),
+            ChangeSource::tool("edit_file"),
+        );
+        let second = manager.save(None).await.unwrap();
+        
+        let journal = manager.journal(&first).unwrap();
+        assert_eq!(journal[0].source.tool.as_deref(), Some("write_file"));
+        assert_eq!(journal[0].source.reason.as_deref(), Some("scaffold binary"));
+        assert_eq!(journal[1].source, ChangeSource::default());
+        assert!(journal[0].timestamp <= journal[1].timestamp);
+        
+        let history = manager.file_history(&file);
+        let sources: Vec<_> = history.iter()
+            .map(|(id, change)| (*id, change.source.tool.clone().unwrap()))
+            .collect();
+        assert_eq!(sources, vec![(first, "write_file".to_string()), (second, "edit_file".to_string())]);
+        
+        let diff = manager.change_diff(&history[1].1).await.unwrap();
+        assert_eq!((diff.insertions, diff.deletions), (3, 1));
+    }
+
+    #[tokio::test]
+    async fn test_journal_survives_reopen() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            ..Default::default()
+        };
+        let file = dir.path().join("a.txt");
+        
+        let id = {
+            let manager = CheckpointManager::new(config.clone());
+            manager.record_tool_change(file.clone(), None, Some("a".into()), ChangeSource::tool("shell"));
+            manager.save(None).await.unwrap()
+        };
+        
+        let manager = CheckpointManager::open(config).await.unwrap();
+        let journal = manager.journal(&id).unwrap();
+        assert_eq!(journal.len(), 1);
+        assert_eq!(journal[0].source.tool.as_deref(), Some("shell"));
+    }
+
+    // === Conversation Provider Tests ===
+
+    /// Conversation held by the "host agent"
+    #[derive(Default)]
+    struct TestConversation {
+        messages: parking_lot::Mutex<Vec<String>>,
+        fail_restore: bool,
+    }
+
+    impl TestConversation {
+        fn say(&self, text: &str) {
+            self.messages.lock().push(text.to_string());
+        }
+    }
+
+    impl ConversationProvider for TestConversation {
+        fn snapshot(&self) -> ConversationSnapshot {
+            ConversationSnapshot {
+                messages: self.messages.lock().iter()
+                    .map(|text| ConversationMessage {
+                        timestamp: DateTime::UNIX_EPOCH,
+                        ..ConversationMessage::new(MessageRole::User, text.clone())
+                    })
+                    .collect(),
+                token_count: 0,
+            }
+        }
+
+        fn restore(&self, snapshot: &ConversationSnapshot) -> Result<(), HookError> {
+            if self.fail_restore {
+                return Err("conversation store unavailable".into());
+            }
+            *self.messages.lock() = snapshot.messages.iter().map(|m| m.content.clone()).collect();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_restore_rewinds_conversation() {
+        let (manager, _dir) = test_manager();
+        let conversation = Arc::new(TestConversation::default());
+        manager.set_conversation_provider(conversation.clone());
+        let task_id = TaskId::new();
+        
+        conversation.say("hello");
+        let first = manager.checkpoint_turn(task_id, 0).await.unwrap();
+        conversation.say("do something risky");
+        manager.checkpoint_turn(task_id, 1).await.unwrap();
+        
+        let snapshot = manager.get(&first).unwrap().data.conversation_snapshot.unwrap();
+        assert_eq!(snapshot.messages.len(), 1);
+        
+        manager.undo().await.unwrap();
+        assert_eq!(*conversation.messages.lock(), vec!["hello".to_string()]);
+        
+        manager.redo().await.unwrap();
+        assert_eq!(conversation.messages.lock().len(), 2);
+        
+        let report = manager.restore_with_policy(first, ConflictPolicy::Abort).await.unwrap();
+        assert!(report.conversation_restored);
+    }
+
+    #[tokio::test]
+    async fn test_conversation_restore_failure_is_reported() {
+        let (manager, _dir) = test_manager();
+        manager.set_conversation_provider(Arc::new(TestConversation {
+            fail_restore: true,
+            ..Default::default()
+        }));
+        
+        let id = manager.save(None).await.unwrap();
+        let report = manager.restore_with_policy(id, ConflictPolicy::Abort).await.unwrap();
+        
+        assert!(!report.conversation_restored);
+        assert_eq!(report.conversation_error.as_deref(), Some("conversation store unavailable"));
+        assert_eq!(manager.current(), Some(id));
+    }
+
+    #[tokio::test]
+    async fn test_no_provider_no_snapshot() {
+        let (manager, _dir) = test_manager();
+        
+        let id = manager.save(None).await.unwrap();
+        
+        assert!(manager.get(&id).unwrap().data.conversation_snapshot.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rewind_conversation_keeps_files() {
+        let (manager, dir) = test_manager();
+        let conversation = Arc::new(TestConversation::default());
+        manager.set_conversation_provider(conversation.clone());
+        let task_id = TaskId::new();
+        let file = dir.path().join("good.rs");
+        
+        conversation.say("write good code");
+        manager.checkpoint_turn(task_id, 0).await.unwrap();
+        
+        std::fs::write(&file, "good code").unwrap();
+        manager.record_file_change(file.clone(), None, "good code".to_string());
+        conversation.say("now ramble off topic");
+        let latest = manager.checkpoint_turn(task_id, 1).await.unwrap();
+        
+        manager.rewind_conversation(task_id, 0).await.unwrap();
+        
+        assert_eq!(*conversation.messages.lock(), vec!["write good code".to_string()]);
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "good code");
+        assert_eq!(manager.current(), Some(latest));
+    }
+
+    #[tokio::test]
+    async fn test_rewind_conversation_errors() {
+        let (manager, _dir) = test_manager();
+        let task_id = TaskId::new();
+        manager.checkpoint_turn(task_id, 0).await.unwrap();
+        
+        assert!(matches!(
+            manager.rewind_conversation(task_id, 5).await,
+            Err(CheckpointError::TurnNotFound(_, 5))
+        ));
+        assert!(matches!(
+            manager.rewind_conversation(TaskId::new(), 0).await,
+            Err(CheckpointError::TurnNotFound(_, 0))
+        ));
+        assert!(matches!(
+            manager.rewind_conversation(task_id, 0).await,
+            Err(CheckpointError::NoConversationSnapshot(_))
+        ));
+        
+        manager.set_conversation_provider(Arc::new(TestConversation::default()));
+        manager.checkpoint_turn(task_id, 1).await.unwrap();
+        manager.clear_conversation_provider();
+        assert!(matches!(
+            manager.rewind_conversation(task_id, 1).await,
+            Err(CheckpointError::NoConversationProvider)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_restore_files_only_keeps_conversation() {
+        let (manager, dir) = test_manager();
+        let conversation = Arc::new(TestConversation::default());
+        manager.set_conversation_provider(conversation.clone());
+        let task_id = TaskId::new();
+        let file = dir.path().join("bad.rs");
+        
+        let first = manager.checkpoint_turn(task_id, 0).await.unwrap();
+        std::fs::write(&file, "bad edit").unwrap();
+        manager.record_file_change(file.clone(), None, "bad edit".to_string());
+        conversation.say("useful discussion");
+        manager.checkpoint_turn(task_id, 1).await.unwrap();
+        
+        let report = manager.restore_files_only(first).await.unwrap();
+        
+        assert!(!report.conversation_restored);
+        assert!(!file.exists());
+        assert_eq!(*conversation.messages.lock(), vec!["useful discussion".to_string()]);
+        assert_eq!(manager.current(), Some(first));
+        assert!(manager.can_redo());
+    }
+
+    #[tokio::test]
+    async fn test_conversation_stored_incrementally() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            ..Default::default()
+        };
+        let task_id = TaskId::new();
+        
+        let (first, second, third) = {
+            let manager = CheckpointManager::new(config.clone());
+            let conversation = Arc::new(TestConversation::default());
+            manager.set_conversation_provider(conversation.clone());
+            
+            conversation.say("a long system prompt");
+            let first = manager.checkpoint_turn(task_id, 0).await.unwrap();
+            conversation.say("turn one");
+            let second = manager.checkpoint_turn(task_id, 1).await.unwrap();
+            conversation.messages.lock().pop();
+            conversation.say("turn one, rephrased");
+            let third = manager.checkpoint_turn(task_id, 2).await.unwrap();
+            (first, second, third)
+        };
+        
+        let stored = |id: CheckpointId| {
+            let json = std::fs::read_to_string(dir.path().join("checkpoints").join(format!("{}.json", id))).unwrap();
+            serde_json::from_str::<Checkpoint>(&json).unwrap().data
+        };
+        assert!(stored(first).conversation_snapshot.is_some());
+        let delta = stored(second).conversation_delta.unwrap();
+        assert_eq!((delta.kept, delta.appended.len()), (1, 1));
+        assert!(stored(second).conversation_snapshot.is_none());
+        assert_eq!(stored(third).conversation_delta.unwrap().kept, 1);
+        
+        let manager = CheckpointManager::open(config).await.unwrap();
+        let contents = |id: CheckpointId| -> Vec<String> {
+            let data = manager.get(&id).unwrap().data;
+            assert!(data.conversation_delta.is_none());
+            data.conversation_snapshot.unwrap().messages.into_iter().map(|m| m.content).collect()
+        };
+        assert_eq!(contents(second), vec!["a long system prompt", "turn one"]);
+        assert_eq!(contents(third), vec!["a long system prompt", "turn one, rephrased"]);
+    }
+
+    #[tokio::test]
+    async fn test_pruning_rewrites_conversation_deltas() {
+        let dir = tempdir().unwrap();
+        let config = CheckpointConfig {
+            storage_dir: dir.path().join("checkpoints"),
+            max_checkpoints: 1,
+            ..Default::default()
+        };
+        let task_id = TaskId::new();
+        
+        let second = {
+            let manager = CheckpointManager::new(config.clone());
+            let conversation = Arc::new(TestConversation::default());
+            manager.set_conversation_provider(conversation.clone());
+            
+            conversation.say("hello");
+            manager.checkpoint_turn(task_id, 0).await.unwrap();
+            conversation.say("world");
+            manager.checkpoint_turn(task_id, 1).await.unwrap()
+        };
+        
+        let manager = CheckpointManager::open(config).await.unwrap();
+        assert_eq!(manager.count(), 1);
+        let snapshot = manager.get(&second).unwrap().data.conversation_snapshot.unwrap();
+        assert_eq!(snapshot.messages.len(), 2);
+    }
+
+    // === Task Tests ===
+
+    #[tokio::test]
+    async fn test_concurrent_tasks_track_turns_separately() {
+        let (manager, _dir) = test_manager();
+        let (task_a, task_b) = (TaskId::new(), TaskId::new());
+        
+        let a2 = manager.checkpoint_turn(task_a, 2).await.unwrap();
+        let a3 = manager.checkpoint_turn(task_a, 3).await.unwrap();
+        let b3 = manager.checkpoint_turn(task_b, 3).await.unwrap();
+        
+        assert_eq!(manager.task_turns(task_a), vec![(2, a2), (3, a3)]);
+        assert_eq!(manager.task_turns(task_b), vec![(3, b3)]);
+        assert_eq!(manager.previous_task_checkpoint(task_a), Some(a2));
+        assert_eq!(manager.previous_task_checkpoint(task_b), None);
+    }
+
+    #[tokio::test]
+    async fn test_undo_task_keeps_other_tasks() {
+        let (manager, dir) = test_manager();
+        let (task_a, task_b) = (TaskId::new(), TaskId::new());
+        let file_a = dir.path().join("a.txt");
+        let file_b = dir.path().join("b.txt");
+        
+        std::fs::write(&file_a, "a1").unwrap();
+        manager.record_file_change(file_a.clone(), None, "a1".to_string());
+        manager.checkpoint_turn(task_a, 1).await.unwrap();
+        
+        std::fs::write(&file_b, "b1").unwrap();
+        manager.record_file_change(file_b.clone(), None, "b1".to_string());
+        manager.checkpoint_turn(task_b, 1).await.unwrap();
+        
+        std::fs::write(&file_a, "a2").unwrap();
+        manager.record_file_change(file_a.clone(), Some("a1".to_string()), "a2".to_string());
+        let a2 = manager.checkpoint_turn(task_a, 2).await.unwrap();
+        
+        let report = manager.undo_task(task_a).await.unwrap();
+        assert_eq!(report.checkpoint_id, a2);
+        assert_eq!(std::fs::read_to_string(&file_a).unwrap(), "a1");
+        assert_eq!(std::fs::read_to_string(&file_b).unwrap(), "b1");
+        
+        manager.undo_task(task_a).await.unwrap();
+        assert!(!file_a.exists());
+        assert_eq!(std::fs::read_to_string(&file_b).unwrap(), "b1");
+        
+        assert!(matches!(manager.undo_task(task_a).await, Err(CheckpointError::NothingToUndo)));
+    }
+
+    #[tokio::test]
+    async fn test_revert_turn_picks_the_named_task() {
+        let (manager, dir) = test_manager();
+        let (task_a, task_b) = (TaskId::new(), TaskId::new());
+        let file_a = dir.path().join("a.txt");
+        let file_b = dir.path().join("b.txt");
+        
+        std::fs::write(&file_a, "a3").unwrap();
+        manager.record_file_change(file_a.clone(), None, "a3".to_string());
+        let a3 = manager.checkpoint_turn(task_a, 3).await.unwrap();
+        
+        std::fs::write(&file_b, "b3").unwrap();
+        manager.record_file_change(file_b.clone(), None, "b3".to_string());
+        manager.checkpoint_turn(task_b, 3).await.unwrap();
+        
+        let report = manager.revert_turn(task_a, 3).await.unwrap();
+        
+        assert_eq!(report.checkpoint_id, a3);
+        assert!(!file_a.exists());
+        assert_eq!(std::fs::read_to_string(&file_b).unwrap(), "b3");
+    }
+
+    // === Agent State Tests ===
+
+    #[tokio::test]
+    async fn test_restore_replays_agent_states() {
+        let (manager, _dir) = test_manager();
+        let memory = Arc::new(parking_lot::Mutex::new(vec!["fact one".to_string()]));
+        
+        let (snap, restore) = (memory.clone(), memory.clone());
+        manager.agent_states().register_fns(
+            "memory",
+            move || snap.lock().clone(),
+            move |facts: Vec<String>| {
+                *restore.lock() = facts;
+                Ok(())
+            },
+        );
+        manager.agent_states().register_fns(
+            "tool_cache",
+            || 0u32,
+            |_: u32| Err("cache is read-only".into()),
+        );
+        
+        let first = manager.save(None).await.unwrap();
+        memory.lock().push("wrong fact".to_string());
+        manager.save(None).await.unwrap();
+        
+        let saved = manager.get(&first).unwrap().data.agent_states;
+        assert_eq!(saved.len(), 2);
+        
+        let report = manager.restore_with_policy(first, ConflictPolicy::Abort).await.unwrap();
+        
+        assert_eq!(*memory.lock(), vec!["fact one".to_string()]);
+        assert_eq!(report.agent_states_restored, vec!["memory".to_string()]);
+        assert_eq!(report.agent_state_errors[0].0, "tool_cache");
+        assert!(report.has_hook_errors());
+    }
+
+    struct Unsnapshottable;
+
+    impl crate::agent_state::AgentState for Unsnapshottable {
+        fn snapshot(&self) -> Result<serde_json::Value, crate::error::HookError> {
+            Err("index is rebuilding".into())
+        }
+
+        fn restore(&self, _state: &serde_json::Value) -> Result<(), crate::error::HookError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_agent_snapshot_is_reported_on_restore() {
+        let (manager, _dir) = test_manager();
+        manager.agent_states().register("search_index", Arc::new(Unsnapshottable));
+        manager.agent_states().register_fns("counter", || 1u32, |_: u32| Ok(()));
+        
+        let id = manager.save(None).await.unwrap();
+        let data = manager.get(&id).unwrap().data;
+        assert!(!data.agent_states.contains_key("search_index"));
+        assert_eq!(data.agent_state_errors["search_index"], "index is rebuilding");
+        
+        let report = manager.restore_with_policy(id, ConflictPolicy::Abort).await.unwrap();
+        
+        assert_eq!(report.agent_states_restored, vec!["counter".to_string()]);
+        assert_eq!(report.agent_states_missing, vec![(
+            "search_index".to_string(),
+            "snapshot failed: index is rebuilding".to_string(),
+        )]);
+        assert!(report.has_hook_errors());
     }
 }
@@ -0,0 +1,313 @@
+//! Line-based three-way merge
+
+use std::path::PathBuf;
+use serde::{Deserialize, Serialize};
+use similar::{DiffTag, TextDiff};
+
+/// Marker opening the checkpoint side of a conflict
+pub const CONFLICT_START: &str = "<<<<<<< checkpoint";
+/// Marker separating the two sides of a conflict
+pub const CONFLICT_SEPARATOR: &str = "=======";
+/// Marker closing the working copy side of a conflict
+pub const CONFLICT_END: &str = ">>>>>>> working copy";
+
+/// Result of merging one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeResult {
+    /// Merged text, with conflict markers where both sides changed the same lines
+    pub text: String,
+    /// Number of conflicting regions
+    pub conflicts: usize,
+}
+
+impl MergeResult {
+    /// Check if the merge had no conflicts
+    pub fn is_clean(&self) -> bool {
+        self.conflicts == 0
+    }
+}
+
+/// Per-file merge outcome reported by a merge restore
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileMerge {
+    /// File path
+    pub path: PathBuf,
+    /// Number of conflicting regions written with markers
+    pub conflicts: usize,
+}
+
+impl FileMerge {
+    /// Check if the file merged without conflicts
+    pub fn is_clean(&self) -> bool {
+        self.conflicts == 0
+    }
+}
+
+/// How to undo one file's change, given its current content
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Revert {
+    /// File already has its pre-change content
+    Unchanged,
+    /// Replace the file (`None` removes it). `merged` holds the number of
+    /// conflicts when later edits had to be merged in.
+    Replace {
+        content: Option<Vec<u8>>,
+        merged: Option<usize>,
+    },
+    /// Later edits cannot be line-merged with the inverse change
+    Unmergeable,
+}
+
+/// Undo the change `before -> after` in `current`.
+///
+/// If the file still has the `after` content it is simply put back;
+/// otherwise the inverse change is three-way merged into it.
+pub(crate) fn revert(before: Option<&[u8]>, after: Option<&[u8]>, current: Option<&[u8]>) -> Revert {
+    if current == before {
+        return Revert::Unchanged;
+    }
+    if current == after {
+        return Revert::Replace { content: before.map(<[u8]>::to_vec), merged: None };
+    }
+
+    fn text(bytes: Option<&[u8]>) -> Option<&str> {
+        bytes.and_then(|b| std::str::from_utf8(b).ok())
+    }
+    let (Some(before), Some(after), Some(current)) = (text(before), text(after), text(current)) else {
+        return Revert::Unmergeable;
+    };
+
+    let result = merge3(after, before, current);
+    Revert::Replace {
+        content: Some(result.text.into_bytes()),
+        merged: Some(result.conflicts),
+    }
+}
+
+/// A change one side made to a range of base lines
+struct Edit<'a> {
+    /// First base line replaced
+    start: usize,
+    /// End of the replaced base range (exclusive)
+    end: usize,
+    /// Replacement lines
+    lines: Vec<&'a str>,
+}
+
+/// Merge two descendants of `base`.
+///
+/// Changes that touch separate regions of `base` are combined; where both
+/// sides changed the same (or adjacent) lines differently, both versions
+/// are kept between conflict markers, checkpoint side first.
+pub fn merge3(base: &str, ours: &str, theirs: &str) -> MergeResult {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let our_edits = edits(base, ours);
+    let their_edits = edits(base, theirs);
+
+    let mut text = String::new();
+    let mut conflicts = 0;
+    let mut cursor = 0;
+    let (mut i, mut j) = (0, 0);
+
+    while i < our_edits.len() || j < their_edits.len() {
+        // Start a cluster with whichever edit comes first
+        let (mut lo, mut hi) = match (our_edits.get(i), their_edits.get(j)) {
+            (Some(a), Some(b)) if b.start < a.start => (b.start, b.end),
+            (Some(a), _) => (a.start, a.end),
+            (None, Some(b)) => (b.start, b.end),
+            (None, None) => unreachable!(),
+        };
+        let (first_i, first_j) = (i, j);
+
+        // Grow it with every edit from either side that overlaps or touches it
+        loop {
+            if let Some(a) = our_edits.get(i).filter(|a| a.start <= hi && lo <= a.end) {
+                lo = lo.min(a.start);
+                hi = hi.max(a.end);
+                i += 1;
+            } else if let Some(b) = their_edits.get(j).filter(|b| b.start <= hi && lo <= b.end) {
+                lo = lo.min(b.start);
+                hi = hi.max(b.end);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        push_lines(&mut text, &base_lines[cursor..lo]);
+        cursor = hi;
+
+        let ours_region = apply(&base_lines, lo, hi, &our_edits[first_i..i]);
+        let theirs_region = apply(&base_lines, lo, hi, &their_edits[first_j..j]);
+
+        if first_i == i {
+            push_lines(&mut text, &theirs_region);
+        } else if first_j == j || ours_region == theirs_region {
+            push_lines(&mut text, &ours_region);
+        } else {
+            conflicts += 1;
+            push_marker(&mut text, CONFLICT_START);
+            push_lines(&mut text, &ours_region);
+            push_marker(&mut text, CONFLICT_SEPARATOR);
+            push_lines(&mut text, &theirs_region);
+            push_marker(&mut text, CONFLICT_END);
+        }
+    }
+
+    push_lines(&mut text, &base_lines[cursor..]);
+    MergeResult { text, conflicts }
+}
+
+/// Line edits turning `base` into `side`, in base order
+fn edits<'a>(base: &str, side: &'a str) -> Vec<Edit<'a>> {
+    let diff = TextDiff::from_lines(base, side);
+    let side_lines = diff.new_slices();
+
+    diff.ops().iter()
+        .filter(|op| op.tag() != DiffTag::Equal)
+        .map(|op| Edit {
+            start: op.old_range().start,
+            end: op.old_range().end,
+            lines: side_lines[op.new_range()].to_vec(),
+        })
+        .collect()
+}
+
+/// One side's version of `base[lo..hi]`
+fn apply<'a>(base: &[&'a str], lo: usize, hi: usize, edits: &[Edit<'a>]) -> Vec<&'a str> {
+    let mut out = Vec::new();
+    let mut cursor = lo;
+    for edit in edits {
+        out.extend_from_slice(&base[cursor..edit.start]);
+        out.extend_from_slice(&edit.lines);
+        cursor = edit.end;
+    }
+    out.extend_from_slice(&base[cursor..hi]);
+    out
+}
+
+fn push_lines(text: &mut String, lines: &[&str]) {
+    for line in lines {
+        text.push_str(line);
+    }
+}
+
+fn push_marker(text: &mut String, marker: &str) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(marker);
+    text.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_no_changes() {
+        let result = merge3("a\nb\n", "a\nb\n", "a\nb\n");
+        assert_eq!(result.text, "a\nb\n");
+        assert!(result.is_clean());
+    }
+
+    #[test]
+    fn test_merge_one_side() {
+        assert_eq!(merge3("a\nb\n", "a\nB\n", "a\nb\n").text, "a\nB\n");
+        assert_eq!(merge3("a\nb\n", "a\nb\n", "A\nb\n").text, "A\nb\n");
+    }
+
+    #[test]
+    fn test_merge_separate_regions() {
+        let base = "1\n2\n3\n4\n5\n6\n";
+        let ours = "one\n2\n3\n4\n5\n6\n";
+        let theirs = "1\n2\n3\n4\n5\nsix\n";
+
+        let result = merge3(base, ours, theirs);
+
+        assert_eq!(result.text, "one\n2\n3\n4\n5\nsix\n");
+        assert!(result.is_clean());
+    }
+
+    #[test]
+    fn test_merge_identical_changes() {
+        let result = merge3("a\nb\nc\n", "a\nX\nc\n", "a\nX\nc\n");
+        assert_eq!(result.text, "a\nX\nc\n");
+        assert!(result.is_clean());
+    }
+
+    #[test]
+    fn test_merge_conflict() {
+        let result = merge3("a\nb\nc\n", "a\nours\nc\n", "a\ntheirs\nc\n");
+
+        assert_eq!(result.conflicts, 1);
+        assert_eq!(result.text, "\
+a
+<<<<<<< checkpoint
+ours
+=======
+theirs
+>>>>>>> working copy
+c
+");
+    }
+
+    #[test]
+    fn test_merge_conflict_and_clean_change() {
+        let base = "1\n2\n3\n4\n5\n6\n7\n";
+        let ours = "1\nours\n3\n4\n5\n6\n7\n";
+        let theirs = "1\ntheirs\n3\n4\n5\n6\n7\nappended\n";
+
+        let result = merge3(base, ours, theirs);
+
+        assert_eq!(result.conflicts, 1);
+        assert!(result.text.ends_with("7\nappended\n"));
+    }
+
+    #[test]
+    fn test_merge_missing_trailing_newline() {
+        let result = merge3("a", "ours", "theirs");
+
+        assert_eq!(result.text, "<<<<<<< checkpoint\nours\n=======\ntheirs\n>>>>>>> working copy\n");
+    }
+
+    #[test]
+    fn test_merge_insertions() {
+        let base = "a\nb\nc\nd\n";
+        let ours = "top\na\nb\nc\nd\n";
+        let theirs = "a\nb\nc\nd\nbottom\n";
+
+        assert_eq!(merge3(base, ours, theirs).text, "top\na\nb\nc\nd\nbottom\n");
+    }
+
+    #[test]
+    fn test_revert_untouched_since() {
+        let revert = revert(Some(b"old\n"), Some(b"new\n"), Some(b"new\n"));
+        assert_eq!(revert, Revert::Replace { content: Some(b"old\n".to_vec()), merged: None });
+
+        assert_eq!(super::revert(None, Some(b"x"), Some(b"x")), Revert::Replace { content: None, merged: None });
+        assert_eq!(super::revert(Some(b"x"), Some(b"y"), Some(b"x")), Revert::Unchanged);
+    }
+
+    #[test]
+    fn test_revert_keeps_later_edits() {
+        let before = b"a\nb\nc\nd\ne\n";
+        let after = b"a\nB\nc\nd\ne\n";
+        let current = b"a\nB\nc\nd\nE\n";
+
+        let revert = revert(Some(before), Some(after), Some(current));
+
+        assert_eq!(revert, Revert::Replace {
+            content: Some(b"a\nb\nc\nd\nE\n".to_vec()),
+            merged: Some(0),
+        });
+    }
+
+    #[test]
+    fn test_revert_unmergeable() {
+        // Created by the turn, edited later
+        assert_eq!(revert(None, Some(b"x"), Some(b"y")), Revert::Unmergeable);
+        // Modified by the turn, deleted later
+        assert_eq!(revert(Some(b"x"), Some(b"y"), None), Revert::Unmergeable);
+    }
+}
@@ -0,0 +1,460 @@
+//! Path and glob matching for selecting tracked files
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use ignore::gitignore::Gitignore;
+use parking_lot::Mutex;
+use tracing::warn;
+
+use crate::error::CheckpointError;
+
+/// Ignore files honored by [`TrackingFilter`], highest precedence first
+const IGNORE_FILES: [&str; 2] = [".ignore", ".gitignore"];
+
+/// Matches file paths against a list of paths or glob patterns.
+///
+/// Absolute patterns must match the whole path. Relative patterns match
+/// at any depth, so `src/lib.rs` selects `/work/project/src/lib.rs` and
+/// `*.toml` selects every TOML file. `*` does not cross directory
+/// boundaries; use `**` for that.
+#[derive(Debug, Clone)]
+pub struct PathMatcher {
+    set: GlobSet,
+}
+
+impl PathMatcher {
+    /// Build a matcher from paths or glob patterns
+    pub fn new<I, S>(patterns: I) -> Result<Self, CheckpointError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::build(patterns, |pattern| Ok(format!("**/{}", pattern)))
+    }
+
+    /// Build a matcher whose relative patterns match at any depth below
+    /// `root` (anywhere if there is no root), never in its parents
+    pub fn under<I, S>(root: Option<&Path>, patterns: I) -> Result<Self, CheckpointError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let Some(root) = root else {
+            return Self::new(patterns);
+        };
+        Self::build(patterns, |pattern| Ok(format!("{}/**/{}", escape_root(root), pattern)))
+    }
+
+    /// Build a matcher whose relative patterns are resolved against `root`
+    /// instead of matching at any depth. Fails with
+    /// [`CheckpointError::NoWorkspaceRoot`] if a pattern is relative and
+    /// there is no root.
+    pub fn rooted<I, S>(root: Option<&Path>, patterns: I) -> Result<Self, CheckpointError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::build(patterns, |pattern| match root {
+            Some(root) => Ok(format!("{}/{}", escape_root(root), pattern)),
+            None => Err(CheckpointError::NoWorkspaceRoot(pattern.to_string())),
+        })
+    }
+
+    /// Build a matcher, turning relative patterns (without a leading `./`)
+    /// into absolute globs with `anchor`
+    fn build<I, S>(
+        patterns: I,
+        anchor: impl Fn(&str) -> Result<String, CheckpointError>,
+    ) -> Result<Self, CheckpointError>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let pattern = pattern.as_ref();
+            let anchored = if is_anchored(pattern) {
+                pattern.to_string()
+            } else {
+                anchor(pattern.trim_start_matches("./"))?
+            };
+
+            let glob = GlobBuilder::new(&anchored)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| CheckpointError::InvalidPattern(format!("{}: {}", pattern, e)))?;
+            builder.add(glob);
+        }
+
+        let set = builder.build()
+            .map_err(|e| CheckpointError::InvalidPattern(e.to_string()))?;
+        Ok(Self { set })
+    }
+
+    /// Check if a path is selected
+    pub fn matches(&self, path: &Path) -> bool {
+        self.set.is_match(path)
+    }
+
+    /// Check if a path or any directory containing it is selected
+    pub fn matches_or_ancestor(&self, path: &Path) -> bool {
+        path.ancestors().any(|p| self.set.is_match(p))
+    }
+
+    /// Check if no patterns were given
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty()
+    }
+}
+
+impl Default for PathMatcher {
+    fn default() -> Self {
+        Self { set: GlobSet::empty() }
+    }
+}
+
+/// Check if a pattern matches the same paths wherever it is anchored
+fn is_anchored(pattern: &str) -> bool {
+    Path::new(pattern).has_root() || pattern.starts_with("**")
+}
+
+/// Build the exclude matcher of a [`TrackingFilter`]. Without a root,
+/// relative patterns are checked but not applied, as they would also
+/// match the directories above the workspace.
+fn exclude_matcher<S: AsRef<str>>(root: Option<&Path>, exclude: &[S]) -> Result<PathMatcher, CheckpointError> {
+    if root.is_some() {
+        return PathMatcher::under(root, exclude);
+    }
+    PathMatcher::new(exclude)?;
+    PathMatcher::new(exclude.iter().filter(|pattern| is_anchored(pattern.as_ref())))
+}
+
+/// A root directory as a glob prefix matching only itself
+fn escape_root(root: &Path) -> String {
+    globset::escape(root.to_string_lossy().trim_end_matches('/'))
+}
+
+/// Resolve a path to an absolute one that globs anchored on it match.
+///
+/// Relative paths are joined onto the current directory. The longest
+/// existing prefix is canonicalized, so the path need not exist yet.
+pub(crate) fn absolute_path(path: &Path) -> PathBuf {
+    let path = match std::env::current_dir() {
+        Ok(cwd) if path.is_relative() => normalize(&cwd.join(path)),
+        _ => normalize(path),
+    };
+    for existing in path.ancestors() {
+        if let Ok(canonical) = existing.canonicalize() {
+            return match path.strip_prefix(existing) {
+                Ok(rest) if !rest.as_os_str().is_empty() => canonical.join(rest),
+                _ => canonical,
+            };
+        }
+    }
+    path
+}
+
+/// Drop `.` components, such as those in paths reported under a
+/// relative watch root
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    path.components().collect()
+}
+
+/// Decides which files hutch tracks, scans and restores
+///
+/// A file is tracked if:
+/// - it or a directory containing it matches an include pattern (when
+///   there are any), and none matches an exclude pattern; relative
+///   patterns only match below the workspace root, and relative exclude
+///   patterns are not applied without one
+/// - no `.gitignore` or `.ignore` file between it and the workspace root
+///   ignores it
+/// - it is not inside the checkpoint storage directory
+///
+/// Ignore files are read once and cached.
+#[derive(Debug)]
+pub struct TrackingFilter {
+    /// Workspace root that patterns are anchored under and ignore files
+    /// are looked up from
+    root: Option<PathBuf>,
+    /// Honor ignore files under the root
+    respect_ignore_files: bool,
+    /// Include and exclude patterns as given, for re-anchoring
+    patterns: (Vec<String>, Vec<String>),
+    /// Files must match one of these, unless empty
+    include: PathMatcher,
+    /// Files and directories matching these are skipped
+    exclude: PathMatcher,
+    /// Checkpoint storage directory, never tracked
+    storage_dir: PathBuf,
+    /// Parsed ignore files by directory (`None` if it has none)
+    ignore_files: Mutex<HashMap<PathBuf, Option<Arc<Vec<Gitignore>>>>>,
+}
+
+impl TrackingFilter {
+    /// Create a filter, failing on invalid patterns. A relative root is
+    /// resolved against the current directory.
+    pub fn new<S: AsRef<str>>(
+        root: Option<PathBuf>,
+        include: &[S],
+        exclude: &[S],
+        storage_dir: PathBuf,
+    ) -> Result<Self, CheckpointError> {
+        let root = root.map(|root| absolute_path(&root));
+        Ok(Self {
+            include: PathMatcher::under(root.as_deref(), include)?,
+            exclude: exclude_matcher(root.as_deref(), exclude)?,
+            ..Self::empty(root, include, exclude, storage_dir)
+        })
+    }
+
+    /// Create a filter, logging and dropping an include or exclude list
+    /// that has an invalid pattern
+    pub fn lenient<S: AsRef<str>>(
+        root: Option<PathBuf>,
+        include: &[S],
+        exclude: &[S],
+        storage_dir: PathBuf,
+    ) -> Self {
+        let root = root.map(|root| absolute_path(&root));
+        let lenient = |matcher: Result<PathMatcher, CheckpointError>| {
+            matcher.unwrap_or_else(|e| {
+                warn!(error = %e, "Ignoring invalid tracking patterns");
+                PathMatcher::default()
+            })
+        };
+
+        Self {
+            include: lenient(PathMatcher::under(root.as_deref(), include)),
+            exclude: lenient(exclude_matcher(root.as_deref(), exclude)),
+            ..Self::empty(root, include, exclude, storage_dir)
+        }
+    }
+
+    fn empty<S: AsRef<str>>(
+        root: Option<PathBuf>,
+        include: &[S],
+        exclude: &[S],
+        storage_dir: PathBuf,
+    ) -> Self {
+        let owned = |patterns: &[S]| patterns.iter().map(|p| p.as_ref().to_string()).collect();
+        Self {
+            root,
+            respect_ignore_files: true,
+            patterns: (owned(include), owned(exclude)),
+            include: PathMatcher::default(),
+            exclude: PathMatcher::default(),
+            storage_dir,
+            ignore_files: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Set whether `.gitignore` and `.ignore` files are honored
+    pub fn respect_ignore_files(mut self, respect: bool) -> Self {
+        self.respect_ignore_files = respect;
+        self
+    }
+
+    /// Same patterns and settings, anchored under `root` if this filter
+    /// has no root of its own
+    pub fn or_root(&self, root: &Path) -> Self {
+        let root = self.root.clone().unwrap_or_else(|| root.to_path_buf());
+        let (include, exclude) = &self.patterns;
+        Self::lenient(Some(root), include, exclude, self.storage_dir.clone())
+            .respect_ignore_files(self.respect_ignore_files)
+    }
+
+    /// Check if a file is tracked
+    pub fn is_tracked(&self, path: &Path) -> bool {
+        (self.include.is_empty() || self.include.matches_or_ancestor(path))
+            && !self.is_excluded(path, false)
+    }
+
+    /// Check if a file or directory is excluded. Include patterns do not
+    /// apply, so directories holding included files are not excluded.
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        path.starts_with(&self.storage_dir)
+            || self.exclude.matches_or_ancestor(path)
+            || self.is_ignored(path, is_dir)
+    }
+
+    /// Check ignore files from the path's directory up to the root; the
+    /// closest file with a matching rule decides
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(root) = self.root.as_ref().filter(|_| self.respect_ignore_files) else {
+            return false;
+        };
+        if !path.starts_with(root) {
+            return false;
+        }
+
+        let mut dir = path.parent();
+        while let Some(current) = dir.filter(|d| d.starts_with(root)) {
+            for ignore in self.ignore_files_in(current).iter() {
+                let matched = ignore.matched_path_or_any_parents(path, is_dir);
+                if matched.is_ignore() {
+                    return true;
+                }
+                if matched.is_whitelist() {
+                    return false;
+                }
+            }
+            dir = current.parent();
+        }
+        false
+    }
+
+    fn ignore_files_in(&self, dir: &Path) -> Arc<Vec<Gitignore>> {
+        let mut cache = self.ignore_files.lock();
+        let entry = cache.entry(dir.to_path_buf()).or_insert_with(|| {
+            let files: Vec<Gitignore> = IGNORE_FILES.iter()
+                .map(|name| dir.join(name))
+                .filter(|path| path.is_file())
+                .map(|path| Gitignore::new(path).0)
+                .collect();
+            (!files.is_empty()).then(|| Arc::new(files))
+        });
+        entry.clone().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path_matches_at_any_depth() {
+        let matcher = PathMatcher::new(["src/lib.rs"]).unwrap();
+
+        assert!(matcher.matches(Path::new("/work/project/src/lib.rs")));
+        assert!(matcher.matches(Path::new("src/lib.rs")));
+        assert!(!matcher.matches(Path::new("/work/project/src/lib.rs.bak")));
+        assert!(!matcher.matches(Path::new("/work/project/tests/lib.rs")));
+    }
+
+    #[test]
+    fn test_absolute_path_is_anchored() {
+        let matcher = PathMatcher::new(["/work/a.txt"]).unwrap();
+
+        assert!(matcher.matches(Path::new("/work/a.txt")));
+        assert!(!matcher.matches(Path::new("/other/work/a.txt")));
+    }
+
+    #[test]
+    fn test_rooted_patterns_resolve_against_root() {
+        let root = Path::new("/work/[app]");
+        let matcher = PathMatcher::rooted(Some(root), ["src/lib.rs", "*.toml"]).unwrap();
+
+        assert!(matcher.matches(Path::new("/work/[app]/src/lib.rs")));
+        assert!(matcher.matches(Path::new("/work/[app]/Cargo.toml")));
+        assert!(!matcher.matches(Path::new("/work/[app]/crates/core/src/lib.rs")));
+        assert!(!matcher.matches(Path::new("/work/[app]/crates/core/Cargo.toml")));
+
+        assert!(PathMatcher::rooted(None, ["/work/a.txt"]).is_ok());
+        assert!(matches!(
+            PathMatcher::rooted(None, ["src/lib.rs"]),
+            Err(CheckpointError::NoWorkspaceRoot(_))
+        ));
+    }
+
+    #[test]
+    fn test_globs() {
+        let matcher = PathMatcher::new(["*.toml", "src/**/*.rs"]).unwrap();
+
+        assert!(matcher.matches(Path::new("/p/Cargo.toml")));
+        assert!(matcher.matches(Path::new("/p/src/a/b/c.rs")));
+        assert!(!matcher.matches(Path::new("/p/README.md")));
+    }
+
+    #[test]
+    fn test_invalid_pattern() {
+        assert!(matches!(
+            PathMatcher::new(["src/[lib.rs"]),
+            Err(CheckpointError::InvalidPattern(_))
+        ));
+    }
+
+    #[test]
+    fn test_tracking_filter_patterns() {
+        let filter = TrackingFilter::new(
+            Some(PathBuf::from("/p")),
+            &["src/**"],
+            &["*.tmp"],
+            PathBuf::from("/p/.lair"),
+        ).unwrap();
+
+        assert!(filter.is_tracked(Path::new("/p/src/lib.rs")));
+        assert!(!filter.is_tracked(Path::new("/p/README.md")));
+        assert!(!filter.is_tracked(Path::new("/p/src/scratch.tmp")));
+        assert!(!filter.is_tracked(Path::new("/p/.lair/HEAD")));
+        assert!(!filter.is_excluded(Path::new("/p/docs"), true));
+        
+        let filter = TrackingFilter::new(Some(PathBuf::from("/p")), &["src"], &["target"], PathBuf::from("/p/.lair")).unwrap();
+        assert!(filter.is_tracked(Path::new("/p/src/a/b.rs")));
+        assert!(!filter.is_tracked(Path::new("/p/src/target/out")));
+    }
+
+    #[test]
+    fn test_tracking_filter_relative_excludes_need_root() {
+        let filter = TrackingFilter::new(
+            None,
+            &["src"],
+            &["target", "/p/secrets/**"],
+            PathBuf::from("/p/.lair"),
+        ).unwrap();
+
+        assert!(filter.is_tracked(Path::new("/home/me/target/proj/src/lib.rs")));
+        assert!(!filter.is_tracked(Path::new("/p/secrets/src/key")));
+        assert!(!filter.is_tracked(Path::new("/p/.lair/src/HEAD")));
+        assert!(filter.or_root(Path::new("/p")).is_excluded(Path::new("/p/target"), true));
+        assert!(TrackingFilter::new(None, &["src"], &["[target"], PathBuf::from("/p/.lair")).is_err());
+    }
+
+    #[test]
+    fn test_tracking_filter_patterns_stay_below_root() {
+        let root = PathBuf::from("/home/me/target/proj");
+        let filter = TrackingFilter::new(
+            Some(root.clone()),
+            &["src"],
+            &["target", ".git"],
+            root.join(".lair"),
+        ).unwrap();
+
+        assert!(filter.is_tracked(&root.join("src/lib.rs")));
+        assert!(!filter.is_tracked(&root.join("src/target/out")));
+        assert!(!filter.is_tracked(&root.join("docs/src.md")));
+        
+        let filter = TrackingFilter::new(None, &["src"], &["target"], PathBuf::from("/x/.lair")).unwrap();
+        assert!(filter.or_root(&root).is_tracked(&root.join("src/lib.rs")));
+        assert!(filter.is_tracked(&root.join("src/lib.rs")));
+    }
+
+    #[test]
+    fn test_tracking_filter_lenient() {
+        let filter = TrackingFilter::lenient(Some(PathBuf::from("/p")), &["src/[lib"], &["*.tmp"], PathBuf::from("/p/.lair"));
+
+        assert!(filter.is_tracked(Path::new("/p/README.md")));
+        assert!(!filter.is_tracked(Path::new("/p/scratch.tmp")));
+    }
+
+    #[test]
+    fn test_tracking_filter_ignore_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("web")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n*.log\n").unwrap();
+        std::fs::write(root.join("web").join(".ignore"), "dist\n!keep.log\n").unwrap();
+
+        let none: &[&str] = &[];
+        let filter = TrackingFilter::new(Some(root.to_path_buf()), none, none, root.join(".lair")).unwrap();
+
+        assert!(filter.is_excluded(&root.join("target"), true));
+        assert!(!filter.is_tracked(&root.join("target/debug/app")));
+        assert!(!filter.is_tracked(&root.join("build.log")));
+        assert!(!filter.is_tracked(&root.join("web/dist/app.js")));
+        assert!(filter.is_tracked(&root.join("web/keep.log")));
+        assert!(filter.is_tracked(&root.join("src/main.rs")));
+    }
+}
@@ -0,0 +1,611 @@
+//! Restore planning and all-or-nothing application
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::{debug, warn};
+
+use warhorn::CheckpointId;
+use crate::error::CheckpointError;
+use crate::merge::FileMerge;
+
+/// What restoring a checkpoint would do to the workspace, computed
+/// without touching any files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestorePlan {
+    /// Checkpoint that would be restored
+    pub checkpoint_id: CheckpointId,
+    /// Existing files whose content would be replaced
+    pub overwrite: Vec<PathBuf>,
+    /// Missing files that would be written
+    pub create: Vec<PathBuf>,
+    /// Files that would be removed
+    pub delete: Vec<PathBuf>,
+    /// Bytes that would be written
+    pub bytes_to_write: u64,
+    /// Bytes currently on disk in files that would be removed
+    pub bytes_to_delete: u64,
+    /// Files the restore would touch whose on-disk content no longer
+    /// matches what hutch last recorded
+    pub externally_modified: Vec<PathBuf>,
+}
+
+impl RestorePlan {
+    /// Create an empty plan for a checkpoint
+    pub fn new(checkpoint_id: CheckpointId) -> Self {
+        Self {
+            checkpoint_id,
+            overwrite: Vec::new(),
+            create: Vec::new(),
+            delete: Vec::new(),
+            bytes_to_write: 0,
+            bytes_to_delete: 0,
+            externally_modified: Vec::new(),
+        }
+    }
+
+    /// Check if restoring would change nothing on disk
+    pub fn is_empty(&self) -> bool {
+        self.overwrite.is_empty() && self.create.is_empty() && self.delete.is_empty()
+    }
+
+    /// Get total number of files that would be touched
+    pub fn total_files(&self) -> usize {
+        self.overwrite.len() + self.create.len() + self.delete.len()
+    }
+
+    /// Check if any touched file was modified outside of hutch
+    pub fn has_external_modifications(&self) -> bool {
+        !self.externally_modified.is_empty()
+    }
+
+    pub(crate) fn sort(&mut self) {
+        self.overwrite.sort();
+        self.create.sort();
+        self.delete.sort();
+        self.externally_modified.sort();
+    }
+}
+
+/// How a restore treats files modified outside of hutch since it last
+/// recorded them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ConflictPolicy {
+    /// Restore nothing and report the conflicting files
+    #[default]
+    Abort,
+    /// Overwrite external modifications with the checkpoint state
+    Overwrite,
+    /// Restore everything except the conflicting files
+    SkipConflicting,
+    /// Three-way merge the checkpoint content with external edits, using
+    /// the content hutch last recorded as the base; overlapping edits are
+    /// written with conflict markers. Files that cannot be line-merged
+    /// (binary content or deletions) are skipped.
+    Merge,
+}
+
+/// Outcome of a successful restore
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RestoreReport {
+    /// Checkpoint that was restored
+    pub checkpoint_id: CheckpointId,
+    /// Files that were written or removed
+    pub changed: Vec<PathBuf>,
+    /// Conflicting files that were left untouched
+    pub skipped: Vec<PathBuf>,
+    /// Conflicting files that were three-way merged
+    pub merged: Vec<FileMerge>,
+    /// Whether the conversation was rewound to the checkpoint's snapshot
+    #[serde(default)]
+    pub conversation_restored: bool,
+    /// Error from the conversation provider, if rewinding it failed
+    #[serde(default)]
+    pub conversation_error: Option<String>,
+    /// Agent state participants that restored their state
+    #[serde(default)]
+    pub agent_states_restored: Vec<String>,
+    /// Agent state participants that failed to restore, with the error
+    #[serde(default)]
+    pub agent_state_errors: Vec<(String, String)>,
+    /// Agent state participants the checkpoint has no state for, with the
+    /// reason. They keep their current state.
+    #[serde(default)]
+    pub agent_states_missing: Vec<(String, String)>,
+}
+
+impl RestoreReport {
+    /// Check if any conflicting file was skipped
+    pub fn has_skipped(&self) -> bool {
+        !self.skipped.is_empty()
+    }
+
+    /// Check if the conversation or any agent state failed to restore
+    pub fn has_hook_errors(&self) -> bool {
+        self.conversation_error.is_some()
+            || !self.agent_state_errors.is_empty()
+            || !self.agent_states_missing.is_empty()
+    }
+
+    /// Check if any merged file was left with conflict markers
+    pub fn has_merge_conflicts(&self) -> bool {
+        self.merged.iter().any(|m| !m.is_clean())
+    }
+}
+
+/// Outcome of reverting a single turn
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RevertReport {
+    /// Turn that was reverted
+    pub turn_number: u32,
+    /// Checkpoint taken at the end of that turn
+    pub checkpoint_id: CheckpointId,
+    /// Files that were written or removed
+    pub changed: Vec<PathBuf>,
+    /// Files later turns also edited, reverted by three-way merge
+    pub merged: Vec<FileMerge>,
+    /// Files whose later edits could not be merged; left untouched
+    pub skipped: Vec<PathBuf>,
+}
+
+impl RevertReport {
+    /// Check if any file needs manual attention
+    pub fn has_conflicts(&self) -> bool {
+        !self.skipped.is_empty() || self.merged.iter().any(|m| !m.is_clean())
+    }
+}
+
+/// Details of a restore that failed part-way
+#[derive(Debug, Clone, Default)]
+pub struct RestoreFailure {
+    /// Files that could not be restored, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+    /// Files that had already been changed and were put back
+    pub rolled_back: Vec<PathBuf>,
+    /// Files that could not be put back; the workspace may be inconsistent
+    pub rollback_failed: Vec<(PathBuf, String)>,
+}
+
+impl RestoreFailure {
+    /// Check if every change was rolled back
+    pub fn is_clean(&self) -> bool {
+        self.rollback_failed.is_empty()
+    }
+}
+
+impl fmt::Display for RestoreFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed: Vec<String> = self.failed.iter()
+            .map(|(path, reason)| format!("{} ({})", path.display(), reason))
+            .collect();
+        write!(f, "failed to restore {}", failed.join(", "))?;
+
+        if self.is_clean() {
+            write!(f, "; rolled back {} file(s)", self.rolled_back.len())
+        } else {
+            let stuck: Vec<String> = self.rollback_failed.iter()
+                .map(|(path, reason)| format!("{} ({})", path.display(), reason))
+                .collect();
+            write!(f, "; could not roll back {}", stuck.join(", "))
+        }
+    }
+}
+
+/// A pending change to one file
+struct FileOp {
+    /// Target path
+    path: PathBuf,
+    /// Content to write, or None to remove the file
+    content: Option<Vec<u8>>,
+    /// Content before the transaction, or None if the file did not exist
+    backup: Option<Vec<u8>>,
+    /// Permissions before the transaction, restored on rollback
+    permissions: Option<std::fs::Permissions>,
+    /// Temporary file holding the staged content
+    staged: Option<PathBuf>,
+}
+
+/// A set of file writes and removals applied all-or-nothing
+///
+/// New content is first staged into temporary files next to each target,
+/// then renamed into place. If any step fails, every file already changed
+/// is put back the way it was.
+#[derive(Default)]
+pub(crate) struct RestoreTransaction {
+    /// Pending changes, applied in order
+    ops: Vec<FileOp>,
+    /// Directories created while staging, outermost first
+    created_dirs: Vec<PathBuf>,
+}
+
+impl RestoreTransaction {
+    /// Create an empty transaction
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a write, skipping it if the file already has this content
+    pub async fn write(&mut self, path: PathBuf, content: Vec<u8>) -> Result<(), CheckpointError> {
+        let backup = read_disk(&path).await?;
+        if backup.as_ref() == Some(&content) {
+            return Ok(());
+        }
+
+        let permissions = permissions(&path).await;
+        self.ops.push(FileOp { path, content: Some(content), backup, permissions, staged: None });
+        Ok(())
+    }
+
+    /// Queue a removal, skipping it if the file does not exist
+    pub async fn remove(&mut self, path: PathBuf) -> Result<(), CheckpointError> {
+        let Some(backup) = read_disk(&path).await? else {
+            return Ok(());
+        };
+
+        let permissions = permissions(&path).await;
+        self.ops.push(FileOp { path, content: None, backup: Some(backup), permissions, staged: None });
+        Ok(())
+    }
+
+    /// Pending changes as (path, current on-disk content)
+    pub fn pending(&self) -> impl Iterator<Item = (&PathBuf, Option<&[u8]>)> {
+        self.ops.iter().map(|op| (&op.path, op.backup.as_deref()))
+    }
+
+    /// Drop pending changes to the given paths
+    pub fn exclude(&mut self, paths: &[PathBuf]) {
+        self.ops.retain(|op| !paths.contains(&op.path));
+    }
+
+    /// Stage and commit all changes
+    pub async fn apply(mut self) -> Result<Vec<PathBuf>, RestoreFailure> {
+        self.stage().await?;
+        self.commit().await
+    }
+
+    /// Write new content to temporary files; nothing visible changes yet
+    pub async fn stage(&mut self) -> Result<(), RestoreFailure> {
+        for idx in 0..self.ops.len() {
+            if self.ops[idx].content.is_none() {
+                continue;
+            }
+
+            if let Err(e) = self.stage_op(idx).await {
+                let failure = RestoreFailure {
+                    failed: vec![(self.ops[idx].path.clone(), e.to_string())],
+                    ..Default::default()
+                };
+                self.discard_staged().await;
+                self.remove_created_dirs().await;
+                return Err(failure);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move staged content into place, rolling back on the first failure
+    pub async fn commit(mut self) -> Result<Vec<PathBuf>, RestoreFailure> {
+        for idx in 0..self.ops.len() {
+            let op = &self.ops[idx];
+            let result = match &op.staged {
+                Some(staged) => fs::rename(staged, &op.path).await,
+                None => fs::remove_file(&op.path).await,
+            };
+
+            if let Err(e) = result {
+                let mut failure = RestoreFailure {
+                    failed: vec![(op.path.clone(), e.to_string())],
+                    ..Default::default()
+                };
+                warn!(path = %op.path.display(), error = %e, "Restore failed, rolling back");
+
+                for op in self.ops[..idx].iter().rev() {
+                    match rollback(op).await {
+                        Ok(()) => failure.rolled_back.push(op.path.clone()),
+                        Err(e) => failure.rollback_failed.push((op.path.clone(), e.to_string())),
+                    }
+                }
+                self.ops.drain(..idx);
+                self.discard_staged().await;
+                self.remove_created_dirs().await;
+                return Err(failure);
+            }
+        }
+
+        Ok(self.ops.into_iter().map(|op| op.path).collect())
+    }
+
+    async fn stage_op(&mut self, idx: usize) -> io::Result<()> {
+        let path = self.ops[idx].path.clone();
+        let parent = path.parent().unwrap_or(Path::new("."));
+
+        // Remember which directories we create so a rollback can remove them
+        let mut missing = Vec::new();
+        let mut dir = Some(parent);
+        while let Some(d) = dir.filter(|d| !d.as_os_str().is_empty() && !d.exists()) {
+            missing.push(d.to_path_buf());
+            dir = d.parent();
+        }
+        fs::create_dir_all(parent).await?;
+        self.created_dirs.extend(missing.into_iter().rev());
+
+        let staged = staging_path(&path);
+        let content = self.ops[idx].content.as_deref().unwrap_or_default();
+        fs::write(&staged, content).await?;
+        self.ops[idx].staged = Some(staged.clone());
+
+        // Keep the original's permissions (e.g. executable scripts)
+        if let Ok(metadata) = fs::metadata(&path).await {
+            fs::set_permissions(&staged, metadata.permissions()).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn discard_staged(&mut self) {
+        for op in &mut self.ops {
+            if let Some(staged) = op.staged.take() {
+                let _ = fs::remove_file(&staged).await;
+            }
+        }
+    }
+
+    async fn remove_created_dirs(&mut self) {
+        // Innermost first; only succeeds for directories left empty
+        for dir in self.created_dirs.drain(..).rev() {
+            let _ = fs::remove_dir(&dir).await;
+        }
+    }
+}
+
+/// Put a file back the way it was before the transaction
+async fn rollback(op: &FileOp) -> io::Result<()> {
+    match &op.backup {
+        Some(content) => {
+            let staged = staging_path(&op.path);
+            fs::write(&staged, content).await?;
+            if let Some(permissions) = &op.permissions {
+                fs::set_permissions(&staged, permissions.clone()).await?;
+            }
+            fs::rename(&staged, &op.path).await
+        }
+        None => match fs::remove_file(&op.path).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        },
+    }?;
+
+    debug!(path = %op.path.display(), "Rolled back file");
+    Ok(())
+}
+
+/// Permissions of an existing file
+async fn permissions(path: &Path) -> Option<std::fs::Permissions> {
+    fs::metadata(path).await.ok().map(|metadata| metadata.permissions())
+}
+
+/// Temporary sibling path used to stage content for `path`
+fn staging_path(path: &Path) -> PathBuf {
+    let name = path.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.hutch-{}", name, uuid::Uuid::new_v4()))
+}
+
+/// Read a file from disk, returning `None` if it does not exist
+pub(crate) async fn read_disk(path: &Path) -> Result<Option<Vec<u8>>, CheckpointError> {
+    match fs::read(path).await {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if matches!(e.kind(), io::ErrorKind::NotFound | io::ErrorKind::NotADirectory) => Ok(None),
+        Err(e) => Err(CheckpointError::RestoreError(format!(
+            "Failed to read {}: {}", path.display(), e
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_plan_empty() {
+        let plan = RestorePlan::new(CheckpointId::new());
+
+        assert!(plan.is_empty());
+        assert_eq!(plan.total_files(), 0);
+        assert!(!plan.has_external_modifications());
+    }
+
+    #[test]
+    fn test_plan_total_files() {
+        let mut plan = RestorePlan::new(CheckpointId::new());
+        plan.overwrite.push(PathBuf::from("/a"));
+        plan.create.push(PathBuf::from("/b"));
+        plan.delete.push(PathBuf::from("/c"));
+
+        assert!(!plan.is_empty());
+        assert_eq!(plan.total_files(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_applies_changes() {
+        let dir = tempdir().unwrap();
+        let keep = dir.path().join("keep.txt");
+        let write = dir.path().join("nested/write.txt");
+        let remove = dir.path().join("remove.txt");
+        std::fs::write(&keep, "same").unwrap();
+        std::fs::write(&remove, "bye").unwrap();
+
+        let mut transaction = RestoreTransaction::new();
+        transaction.write(keep.clone(), b"same".to_vec()).await.unwrap();
+        transaction.write(write.clone(), b"hello".to_vec()).await.unwrap();
+        transaction.remove(remove.clone()).await.unwrap();
+
+        // Unchanged files are skipped
+        assert_eq!(transaction.ops.len(), 2);
+
+        let changed = transaction.apply().await.unwrap();
+        assert_eq!(changed, vec![write.clone(), remove.clone()]);
+        assert_eq!(std::fs::read_to_string(&write).unwrap(), "hello");
+        assert!(!remove.exists());
+        // No staging files left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_exclude() {
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.txt");
+        let b = dir.path().join("b.txt");
+        std::fs::write(&a, "on disk").unwrap();
+
+        let mut transaction = RestoreTransaction::new();
+        transaction.write(a.clone(), b"new a".to_vec()).await.unwrap();
+        transaction.write(b.clone(), b"new b".to_vec()).await.unwrap();
+
+        let pending: Vec<_> = transaction.pending().collect();
+        assert_eq!(pending, vec![(&a, Some(&b"on disk"[..])), (&b, None)]);
+
+        transaction.exclude(std::slice::from_ref(&a));
+        transaction.apply().await.unwrap();
+
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "on disk");
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "new b");
+    }
+
+    #[tokio::test]
+    async fn test_transaction_rolls_back_on_commit_failure() {
+        let dir = tempdir().unwrap();
+        let first = dir.path().join("a.txt");
+        let removed = dir.path().join("b.txt");
+        let created = dir.path().join("new/c.txt");
+        let broken = dir.path().join("z.txt");
+        std::fs::write(&first, "original").unwrap();
+        std::fs::write(&removed, "still here").unwrap();
+
+        let mut transaction = RestoreTransaction::new();
+        transaction.write(first.clone(), b"changed".to_vec()).await.unwrap();
+        transaction.remove(removed.clone()).await.unwrap();
+        transaction.write(created.clone(), b"created".to_vec()).await.unwrap();
+        transaction.write(broken.clone(), b"never".to_vec()).await.unwrap();
+        transaction.stage().await.unwrap();
+
+        // Sabotage the last staged file so its rename fails
+        let staged = transaction.ops[3].staged.clone().unwrap();
+        std::fs::remove_file(staged).unwrap();
+
+        let failure = transaction.commit().await.unwrap_err();
+
+        assert_eq!(failure.failed.len(), 1);
+        assert_eq!(failure.failed[0].0, broken);
+        assert!(failure.is_clean());
+        assert_eq!(failure.rolled_back, vec![created.clone(), removed.clone(), first.clone()]);
+
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "original");
+        assert_eq!(std::fs::read_to_string(&removed).unwrap(), "still here");
+        assert!(!created.exists());
+        assert!(!dir.path().join("new").exists());
+        assert!(!broken.exists());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_transaction_commit_failure_discards_staged_file() {
+        let dir = tempdir().unwrap();
+        let first = dir.path().join("a.txt");
+        let blocked = dir.path().join("b.txt");
+        std::fs::write(&first, "original").unwrap();
+
+        let mut transaction = RestoreTransaction::new();
+        transaction.write(first.clone(), b"changed".to_vec()).await.unwrap();
+        transaction.write(blocked.clone(), b"never".to_vec()).await.unwrap();
+        transaction.stage().await.unwrap();
+
+        // A non-empty directory appears where the file should go
+        std::fs::create_dir(&blocked).unwrap();
+        std::fs::write(blocked.join("inner.txt"), "x").unwrap();
+
+        let failure = transaction.commit().await.unwrap_err();
+
+        assert_eq!(failure.failed[0].0, blocked);
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "original");
+        let names: Vec<String> = std::fs::read_dir(dir.path()).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        assert!(names.iter().all(|name| !name.contains(".hutch-")), "{:?}", names);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_rollback_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let script = dir.path().join("run.sh");
+        let removed = dir.path().join("tool.sh");
+        let broken = dir.path().join("z.txt");
+        for path in [&script, &removed] {
+            std::fs::write(path, "#!/bin/sh").unwrap();
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let mut transaction = RestoreTransaction::new();
+        transaction.write(script.clone(), b"#!/bin/sh\nexit 1".to_vec()).await.unwrap();
+        transaction.remove(removed.clone()).await.unwrap();
+        transaction.write(broken.clone(), b"never".to_vec()).await.unwrap();
+        transaction.stage().await.unwrap();
+        std::fs::remove_file(transaction.ops[2].staged.clone().unwrap()).unwrap();
+
+        transaction.commit().await.unwrap_err();
+
+        for path in [&script, &removed] {
+            assert_eq!(std::fs::read_to_string(path).unwrap(), "#!/bin/sh");
+            assert_eq!(std::fs::metadata(path).unwrap().permissions().mode() & 0o777, 0o755);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transaction_stage_failure_changes_nothing() {
+        let dir = tempdir().unwrap();
+        let first = dir.path().join("a.txt");
+        let blocker = dir.path().join("blocker");
+        std::fs::write(&first, "original").unwrap();
+        std::fs::write(&blocker, "a file, not a directory").unwrap();
+
+        let mut transaction = RestoreTransaction::new();
+        transaction.write(first.clone(), b"changed".to_vec()).await.unwrap();
+        transaction.write(blocker.join("inner.txt"), b"x".to_vec()).await.unwrap();
+
+        let failure = transaction.apply().await.unwrap_err();
+
+        assert_eq!(failure.failed[0].0, blocker.join("inner.txt"));
+        assert!(failure.rolled_back.is_empty());
+        assert_eq!(std::fs::read_to_string(&first).unwrap(), "original");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn test_restore_failure_display() {
+        let failure = RestoreFailure {
+            failed: vec![(PathBuf::from("/z.txt"), "denied".to_string())],
+            rolled_back: vec![PathBuf::from("/a.txt")],
+            rollback_failed: vec![],
+        };
+
+        assert_eq!(failure.to_string(), "failed to restore /z.txt (denied); rolled back 1 file(s)");
+    }
+
+    #[tokio::test]
+    async fn test_read_disk_missing() {
+        let dir = tempdir().unwrap();
+
+        assert_eq!(read_disk(&dir.path().join("missing")).await.unwrap(), None);
+
+        std::fs::write(dir.path().join("present"), "x").unwrap();
+        assert_eq!(read_disk(&dir.path().join("present")).await.unwrap(), Some(b"x".to_vec()));
+    }
+}
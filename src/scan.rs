@@ -0,0 +1,65 @@
+//! Workspace scanning
+
+use std::path::{Path, PathBuf};
+
+use crate::error::CheckpointError;
+use crate::pattern::{PathMatcher, TrackingFilter};
+
+/// List the tracked regular files under `root`, sorted.
+///
+/// Files and directories excluded by `filter` or matching `exclude` are
+/// skipped. Symlinks are not followed.
+pub(crate) async fn scan_workspace(
+    root: &Path,
+    filter: &TrackingFilter,
+    exclude: &PathMatcher,
+) -> Result<Vec<PathBuf>, CheckpointError> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let mut entries = tokio::fs::read_dir(&dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if exclude.matches(&path) {
+                continue;
+            }
+
+            let file_type = entry.file_type().await?;
+            if file_type.is_dir() && !filter.is_excluded(&path, true) {
+                dirs.push(path);
+            } else if file_type.is_file() && filter.is_tracked(&path) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_scan_workspace() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        for path in ["a.txt", "src/lib.rs", "target/debug/app", ".lair/HEAD", "debug.log"] {
+            let path = root.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "x").unwrap();
+        }
+
+        std::fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+
+        let none: &[&str] = &[];
+        let filter = TrackingFilter::new(Some(root.to_path_buf()), none, none, root.join(".lair")).unwrap();
+        let exclude = PathMatcher::new(["target", ".gitignore"]).unwrap();
+        let files = scan_workspace(root, &filter, &exclude).await.unwrap();
+
+        assert_eq!(files, vec![root.join("a.txt"), root.join("src/lib.rs")]);
+    }
+}
@@ -1,8 +1,9 @@
 //! Checkpoint storage backend
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::fs;
-use tracing::{debug, info};
+use tokio::io::AsyncWriteExt;
+use tracing::debug;
 
 use warhorn::CheckpointId;
 use crate::checkpoint::Checkpoint;
@@ -50,7 +51,7 @@ impl CheckpointStorage {
         
         let size = json.len() as u64;
         
-        fs::write(&path, &json).await
+        write_atomic(&path, json.as_bytes()).await
             .map_err(|e| CheckpointError::StorageError(format!(
                 "Failed to write checkpoint: {}", e
             )))?;
@@ -98,6 +99,36 @@ impl CheckpointStorage {
         Ok(())
     }
 
+    /// Get the on-disk size of a stored checkpoint
+    pub async fn size(&self, id: &CheckpointId) -> Result<u64, CheckpointError> {
+        let metadata = fs::metadata(self.checkpoint_path(id)).await
+            .map_err(|e| CheckpointError::StorageError(format!(
+                "Failed to read checkpoint metadata: {}", e
+            )))?;
+        
+        Ok(metadata.len())
+    }
+
+    /// Persist the current checkpoint pointer
+    pub async fn save_head(&self, id: Option<&CheckpointId>) -> Result<(), CheckpointError> {
+        self.write_pointer(self.base_dir.join("HEAD"), id).await
+    }
+
+    /// Load the persisted current checkpoint pointer, if any
+    pub async fn load_head(&self) -> Result<Option<CheckpointId>, CheckpointError> {
+        self.read_pointer(self.base_dir.join("HEAD")).await
+    }
+
+    /// Persist the tip of the active branch
+    pub async fn save_tip(&self, id: Option<&CheckpointId>) -> Result<(), CheckpointError> {
+        self.write_pointer(self.base_dir.join("TIP"), id).await
+    }
+
+    /// Load the persisted tip of the active branch, if any
+    pub async fn load_tip(&self) -> Result<Option<CheckpointId>, CheckpointError> {
+        self.read_pointer(self.base_dir.join("TIP")).await
+    }
+
     /// Check if a checkpoint exists in storage
     pub async fn exists(&self, id: &CheckpointId) -> bool {
         self.checkpoint_path(id).exists()
@@ -152,10 +183,77 @@ impl CheckpointStorage {
         Ok(total)
     }
 
-    // Private helper
+    // Private helpers
     fn checkpoint_path(&self, id: &CheckpointId) -> PathBuf {
         self.base_dir.join(format!("{}.json", id))
     }
+
+    async fn write_pointer(&self, path: PathBuf, id: Option<&CheckpointId>) -> Result<(), CheckpointError> {
+        match id {
+            Some(id) => {
+                fs::create_dir_all(&self.base_dir).await
+                    .map_err(|e| CheckpointError::StorageError(format!(
+                        "Failed to create directory: {}", e
+                    )))?;
+                write_atomic(&path, id.to_string().as_bytes()).await
+                    .map_err(|e| CheckpointError::StorageError(format!(
+                        "Failed to write {}: {}", path.display(), e
+                    )))?;
+            }
+            None if path.exists() => {
+                fs::remove_file(&path).await
+                    .map_err(|e| CheckpointError::StorageError(format!(
+                        "Failed to delete {}: {}", path.display(), e
+                    )))?;
+            }
+            None => {}
+        }
+        
+        Ok(())
+    }
+
+    async fn read_pointer(&self, path: PathBuf) -> Result<Option<CheckpointId>, CheckpointError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        
+        let contents = fs::read_to_string(&path).await
+            .map_err(|e| CheckpointError::StorageError(format!(
+                "Failed to read {}: {}", path.display(), e
+            )))?;
+        
+        Ok(contents.trim().parse::<uuid::Uuid>().ok().map(CheckpointId::from_uuid))
+    }
+}
+
+/// Write to a temporary file and rename it into place, so a crash never
+/// leaves a truncated file behind. The file is synced before the rename,
+/// and the directory after it, so the rename is never persisted ahead of
+/// the contents.
+pub(crate) async fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let tmp_path = path.with_extension("tmp");
+    let mut file = fs::File::create(&tmp_path).await?;
+    file.write_all(contents).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    fs::rename(&tmp_path, path).await?;
+    sync_parent(path).await
+}
+
+/// Persist the directory entries of a path's parent
+#[cfg(unix)]
+async fn sync_parent(path: &Path) -> std::io::Result<()> {
+    match path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        Some(dir) => fs::File::open(dir).await?.sync_all().await,
+        None => Ok(()),
+    }
+}
+
+/// Directories cannot be opened for syncing on this platform
+#[cfg(not(unix))]
+async fn sync_parent(_path: &Path) -> std::io::Result<()> {
+    Ok(())
 }
 
 #[cfg(test)]
@@ -177,6 +275,7 @@ mod tests {
             timestamp: Utc::now(),
             task_id: None,
             turn_number: None,
+            parent: None,
             data: CheckpointData::default(),
         };
         
@@ -184,5 +283,27 @@ mod tests {
         
         let loaded = storage.load(&checkpoint.id).await.unwrap();
         assert_eq!(loaded.name, checkpoint.name);
+        
+        // Written through a temporary file that is renamed into place
+        let files: Vec<_> = std::fs::read_dir(dir.path()).unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, vec![std::ffi::OsString::from(format!("{}.json", checkpoint.id))]);
+    }
+
+    #[tokio::test]
+    async fn test_storage_head_roundtrip() {
+        let dir = tempdir().unwrap();
+        let storage = CheckpointStorage::new(dir.path().to_path_buf());
+        storage.init().await.unwrap();
+        
+        assert_eq!(storage.load_head().await.unwrap(), None);
+        
+        let id = CheckpointId::new();
+        storage.save_head(Some(&id)).await.unwrap();
+        assert_eq!(storage.load_head().await.unwrap(), Some(id));
+        
+        storage.save_head(None).await.unwrap();
+        assert_eq!(storage.load_head().await.unwrap(), None);
     }
 }
@@ -1,12 +1,19 @@
 //! Turn tracking for conversation history
 
-use std::collections::HashMap;
-use warhorn::CheckpointId;
+use std::collections::{BTreeMap, HashMap};
+use warhorn::{CheckpointId, TaskId};
 
 /// Tracks turns and their associated checkpoints
+///
+/// Turns are numbered per task, so concurrent tasks reporting the same
+/// turn number keep separate checkpoints. Turn lookups always name the task.
 pub struct TurnTracker {
-    /// Turn number to checkpoint mapping
-    turns: HashMap<u32, CheckpointId>,
+    /// Turns and checkpoints of each task
+    task_turns: HashMap<TaskId, BTreeMap<u32, CheckpointId>>,
+    /// Latest turn of each task that has not been undone
+    task_current: HashMap<TaskId, u32>,
+    /// Task that recorded the latest turn
+    current_task: Option<TaskId>,
     /// Current turn number
     current_turn: u32,
     /// Turn count
@@ -17,22 +24,26 @@ impl TurnTracker {
     /// Create a new turn tracker
     pub fn new() -> Self {
         Self {
-            turns: HashMap::new(),
+            task_turns: HashMap::new(),
+            task_current: HashMap::new(),
+            current_task: None,
             current_turn: 0,
             count: 0,
         }
     }
 
-    /// Record a new turn with its checkpoint
-    pub fn record_turn(&mut self, turn_number: u32, checkpoint_id: CheckpointId) {
-        self.turns.insert(turn_number, checkpoint_id);
+    /// Record a new turn of a task with its checkpoint
+    pub fn record_turn(&mut self, task_id: TaskId, turn_number: u32, checkpoint_id: CheckpointId) {
+        self.task_turns.entry(task_id).or_default().insert(turn_number, checkpoint_id);
+        self.task_current.insert(task_id, turn_number);
+        self.current_task = Some(task_id);
         self.current_turn = turn_number;
         self.count = self.count.max(turn_number + 1);
     }
 
-    /// Get checkpoint for a turn
-    pub fn checkpoint_for_turn(&self, turn_number: u32) -> Option<CheckpointId> {
-        self.turns.get(&turn_number).copied()
+    /// Get checkpoint for a task's turn
+    pub fn checkpoint_for_task_turn(&self, task_id: TaskId, turn_number: u32) -> Option<CheckpointId> {
+        self.task_turns.get(&task_id)?.get(&turn_number).copied()
     }
 
     /// Get current turn number
@@ -40,32 +51,58 @@ impl TurnTracker {
         self.current_turn
     }
 
+    /// Get a task's latest turn that has not been undone, with its checkpoint
+    pub fn current_task_turn(&self, task_id: TaskId) -> Option<(u32, CheckpointId)> {
+        let turn = *self.task_current.get(&task_id)?;
+        Some((turn, self.checkpoint_for_task_turn(task_id, turn)?))
+    }
+
     /// Get total turn count
     pub fn count(&self) -> u32 {
         self.count
     }
 
-    /// Get the previous turn's checkpoint
+    /// Get the previous turn's checkpoint of the task that recorded the
+    /// latest turn
     pub fn previous_checkpoint(&self) -> Option<CheckpointId> {
-        if self.current_turn == 0 {
-            None
-        } else {
-            self.checkpoint_for_turn(self.current_turn - 1)
-        }
+        self.previous_task_checkpoint(self.current_task?)
+    }
+
+    /// Get the checkpoint of the turn before a task's current one
+    pub fn previous_task_checkpoint(&self, task_id: TaskId) -> Option<CheckpointId> {
+        let current = *self.task_current.get(&task_id)?;
+        self.task_turns.get(&task_id)?
+            .range(..current)
+            .next_back()
+            .map(|(_, &id)| id)
     }
 
-    /// Get all turns with checkpoints
-    pub fn all_turns(&self) -> Vec<(u32, CheckpointId)> {
-        let mut turns: Vec<_> = self.turns.iter()
-            .map(|(&turn, &id)| (turn, id))
-            .collect();
-        turns.sort_by_key(|(turn, _)| *turn);
-        turns
+    /// Step a task's current turn back to its previous one, returning the
+    /// turn stepped over
+    pub fn rewind_task(&mut self, task_id: TaskId) -> Option<(u32, CheckpointId)> {
+        let current = self.current_task_turn(task_id)?;
+        let previous = self.task_turns.get(&task_id)
+            .and_then(|turns| turns.range(..current.0).next_back())
+            .map(|(&turn, _)| turn);
+        match previous {
+            Some(turn) => self.task_current.insert(task_id, turn),
+            None => self.task_current.remove(&task_id),
+        };
+        Some(current)
+    }
+
+    /// Get all turns of a task with their checkpoints, in turn order
+    pub fn task_turns(&self, task_id: TaskId) -> Vec<(u32, CheckpointId)> {
+        self.task_turns.get(&task_id)
+            .map(|turns| turns.iter().map(|(&turn, &id)| (turn, id)).collect())
+            .unwrap_or_default()
     }
 
     /// Clear all turns
     pub fn clear(&mut self) {
-        self.turns.clear();
+        self.task_turns.clear();
+        self.task_current.clear();
+        self.current_task = None;
         self.current_turn = 0;
         self.count = 0;
     }
@@ -84,16 +121,53 @@ mod tests {
     #[test]
     fn test_turn_tracker() {
         let mut tracker = TurnTracker::new();
+        let task = TaskId::new();
         
         let cp1 = CheckpointId::new();
         let cp2 = CheckpointId::new();
         
-        tracker.record_turn(0, cp1);
-        tracker.record_turn(1, cp2);
+        tracker.record_turn(task, 0, cp1);
+        tracker.record_turn(task, 1, cp2);
         
         assert_eq!(tracker.current_turn(), 1);
         assert_eq!(tracker.count(), 2);
-        assert_eq!(tracker.checkpoint_for_turn(0), Some(cp1));
+        assert_eq!(tracker.checkpoint_for_task_turn(task, 0), Some(cp1));
+        assert_eq!(tracker.checkpoint_for_task_turn(TaskId::new(), 0), None);
         assert_eq!(tracker.previous_checkpoint(), Some(cp1));
     }
+
+    #[test]
+    fn test_concurrent_tasks_keep_their_turns() {
+        let mut tracker = TurnTracker::new();
+        let (a, b) = (TaskId::new(), TaskId::new());
+        let (a2, a3, b3) = (CheckpointId::new(), CheckpointId::new(), CheckpointId::new());
+        
+        tracker.record_turn(a, 2, a2);
+        tracker.record_turn(a, 3, a3);
+        tracker.record_turn(b, 3, b3);
+        
+        assert_eq!(tracker.checkpoint_for_task_turn(a, 3), Some(a3));
+        assert_eq!(tracker.checkpoint_for_task_turn(b, 3), Some(b3));
+        assert_eq!(tracker.task_turns(a), vec![(2, a2), (3, a3)]);
+        assert_eq!(tracker.previous_task_checkpoint(a), Some(a2));
+        assert_eq!(tracker.previous_task_checkpoint(b), None);
+        assert_eq!(tracker.previous_checkpoint(), None);
+    }
+
+    #[test]
+    fn test_rewind_task() {
+        let mut tracker = TurnTracker::new();
+        let (a, b) = (TaskId::new(), TaskId::new());
+        let (a0, a1, b1) = (CheckpointId::new(), CheckpointId::new(), CheckpointId::new());
+        tracker.record_turn(a, 0, a0);
+        tracker.record_turn(a, 1, a1);
+        tracker.record_turn(b, 1, b1);
+        
+        assert_eq!(tracker.rewind_task(a), Some((1, a1)));
+        assert_eq!(tracker.current_task_turn(a), Some((0, a0)));
+        assert_eq!(tracker.current_task_turn(b), Some((1, b1)));
+        assert_eq!(tracker.rewind_task(a), Some((0, a0)));
+        assert_eq!(tracker.rewind_task(a), None);
+        assert_eq!(tracker.task_turns(a).len(), 2);
+    }
 }
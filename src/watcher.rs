@@ -0,0 +1,285 @@
+//! Filesystem watcher that records workspace changes automatically
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use crate::error::CheckpointError;
+use crate::manager::CheckpointManager;
+use crate::pattern::{absolute_path, normalize, PathMatcher};
+use crate::restore::read_disk;
+
+/// Marker in the names of files staged by restores
+const STAGING_MARKER: &str = ".hutch-";
+
+/// Configuration for the filesystem watcher
+#[derive(Debug, Clone)]
+pub struct WatchConfig {
+    /// Workspace root to watch recursively; a relative root is resolved
+    /// against the current directory
+    pub root: PathBuf,
+    /// Quiet period to wait for before recording a burst of events
+    pub debounce: Duration,
+    /// Paths or globs to ignore; relative ones match below `root` (see
+    /// [`PathMatcher::under`])
+    pub ignore: Vec<String>,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::from("."),
+            debounce: Duration::from_millis(200),
+            ignore: vec![
+                ".git/**".to_string(),
+                "target/**".to_string(),
+                "node_modules/**".to_string(),
+            ],
+        }
+    }
+}
+
+/// Watches a workspace and feeds changes into a [`CheckpointManager`]
+///
+/// Changed files are read once events settle and recorded against the
+/// last known state, so edits made outside the agent's tools (shell
+/// commands, editors) end up in checkpoints. The checkpoint storage
+/// directory is always ignored.
+///
+/// Edits to existing files hutch has not recorded yet are skipped, as
+/// their earlier content is unknown; call
+/// [`CheckpointManager::snapshot_workspace`] first to track them.
+pub struct FileWatcher {
+    /// Underlying OS watcher; dropping it closes the event channel
+    watcher: RecommendedWatcher,
+    /// Task recording debounced events
+    task: JoinHandle<()>,
+}
+
+impl FileWatcher {
+    /// Start watching `config.root`
+    pub fn start(manager: Arc<CheckpointManager>, config: WatchConfig) -> Result<Self, CheckpointError> {
+        let root = absolute_path(&config.root);
+        let ignore = PathMatcher::under(Some(&root), &config.ignore)?;
+        let (tx, rx) = mpsc::unbounded_channel();
+
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+            match res {
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    let created = matches!(event.kind, EventKind::Create(_));
+                    for path in event.paths {
+                        let _ = tx.send((path, created));
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => warn!(error = %e, "Filesystem watcher error"),
+            }
+        }).map_err(|e| CheckpointError::WatchError(e.to_string()))?;
+
+        watcher.watch(&root, RecursiveMode::Recursive)
+            .map_err(|e| CheckpointError::WatchError(e.to_string()))?;
+
+        debug!(root = %root.display(), "Started filesystem watcher");
+
+        let task = tokio::spawn(record_events(manager, rx, ignore, config.debounce));
+        Ok(Self { watcher, task })
+    }
+
+    /// Stop watching, recording any events still waiting on the debounce
+    pub async fn stop(self) {
+        drop(self.watcher);
+        let _ = self.task.await;
+    }
+}
+
+/// Collect events into batches separated by `debounce` of quiet, and
+/// record each batch. A path counts as created if its first event in the
+/// batch was a creation, so a file removed and recreated is not.
+async fn record_events(
+    manager: Arc<CheckpointManager>,
+    mut rx: mpsc::UnboundedReceiver<(PathBuf, bool)>,
+    ignore: PathMatcher,
+    debounce: Duration,
+) {
+    let storage_dir = manager.storage_dir().to_path_buf();
+    let mut batch = BTreeMap::new();
+
+    while let Some((path, created)) = rx.recv().await {
+        batch.entry(normalize(&path)).or_insert(created);
+
+        let open = loop {
+            match tokio::time::timeout(debounce, rx.recv()).await {
+                Ok(Some((path, created))) => {
+                    batch.entry(normalize(&path)).or_insert(created);
+                }
+                Ok(None) => break false,
+                Err(_) => break true,
+            }
+        };
+
+        for (path, created) in std::mem::take(&mut batch) {
+            if is_ignored(&path, &ignore, &storage_dir) {
+                continue;
+            }
+            record_path(&manager, path, created).await;
+        }
+
+        if !open {
+            break;
+        }
+    }
+}
+
+async fn record_path(manager: &CheckpointManager, path: PathBuf, created: bool) {
+    if tokio::fs::metadata(&path).await.is_ok_and(|m| m.is_dir()) {
+        return;
+    }
+
+    match read_disk(&path).await {
+        Ok(content) => {
+            if manager.record_observed_change(path.clone(), content, created) {
+                debug!(path = %path.display(), "Recorded observed change");
+            }
+        }
+        Err(e) => warn!(path = %path.display(), error = %e, "Failed to read changed file"),
+    }
+}
+
+fn is_ignored(path: &Path, ignore: &PathMatcher, storage_dir: &Path) -> bool {
+    let staged = path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.') && name.contains(STAGING_MARKER));
+
+    staged || path.starts_with(storage_dir) || ignore.matches(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_tracker::FileContent;
+    use crate::manager::CheckpointConfig;
+    use tempfile::tempdir;
+
+    async fn wait_for(mut done: impl FnMut() -> bool) {
+        for _ in 0..100 {
+            if done() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("timed out waiting for watcher");
+    }
+
+    #[test]
+    fn test_is_ignored() {
+        let ignore = PathMatcher::new(["target/**"]).unwrap();
+        let storage = Path::new("/work/.lair/checkpoints");
+
+        assert!(is_ignored(Path::new("/work/target/debug/app"), &ignore, storage));
+        assert!(is_ignored(Path::new("/work/.lair/checkpoints/HEAD"), &ignore, storage));
+        assert!(is_ignored(Path::new("/work/src/.lib.rs.hutch-1234"), &ignore, storage));
+        assert!(!is_ignored(Path::new("/work/src/lib.rs"), &ignore, storage));
+    }
+
+    #[tokio::test]
+    async fn test_watcher_records_changes() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let manager = Arc::new(CheckpointManager::new(CheckpointConfig {
+            storage_dir: root.join(".lair"),
+            ..Default::default()
+        }));
+        let watcher = FileWatcher::start(manager.clone(), WatchConfig {
+            root: root.clone(),
+            debounce: Duration::from_millis(20),
+            ..Default::default()
+        }).unwrap();
+
+        let file = root.join("shell-output.txt");
+        let ignored = root.join("target").join("build.log");
+        std::fs::create_dir_all(ignored.parent().unwrap()).unwrap();
+        std::fs::write(&ignored, "noise").unwrap();
+        std::fs::write(&file, "written by a shell tool").unwrap();
+
+        wait_for(|| manager.file_state(&file).is_some()).await;
+        watcher.stop().await;
+
+        let id = manager.save(None).await.unwrap();
+        let data = manager.get(&id).unwrap().data;
+        assert!(data.file_states.contains_key(&file));
+        assert!(!data.file_states.contains_key(&ignored));
+        assert!(data.created_files.contains(&file));
+        assert_eq!(manager.file_state(&file), Some(FileContent::from("written by a shell tool")));
+    }
+
+    #[tokio::test]
+    async fn test_watcher_resolves_relative_root() {
+        // A relative root, as in the default config, under the current directory
+        let dir = tempfile::tempdir_in(".").unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let relative = Path::new(".").join(dir.path().file_name().unwrap());
+        let manager = Arc::new(CheckpointManager::new(CheckpointConfig {
+            storage_dir: root.join(".lair"),
+            ..Default::default()
+        }));
+        let watcher = FileWatcher::start(manager.clone(), WatchConfig {
+            root: relative,
+            debounce: Duration::from_millis(20),
+            ignore: vec!["*.log".to_string(), "build/**".to_string()],
+        }).unwrap();
+        
+        let log = root.join("debug.log");
+        let built = root.join("build").join("out.bin");
+        let file = root.join("notes.txt");
+        std::fs::create_dir_all(built.parent().unwrap()).unwrap();
+        std::fs::write(&log, "noise").unwrap();
+        std::fs::write(&built, "noise").unwrap();
+        std::fs::write(&file, "kept").unwrap();
+        
+        wait_for(|| manager.file_state(&file).is_some()).await;
+        watcher.stop().await;
+        
+        let id = manager.save(None).await.unwrap();
+        let tracked: Vec<_> = manager.get(&id).unwrap().data.file_states.into_keys().collect();
+        assert_eq!(tracked, vec![file]);
+    }
+
+    #[tokio::test]
+    async fn test_watcher_does_not_delete_existing_files_on_restore() {
+        let dir = tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let readme = root.join("README.md");
+        std::fs::write(&readme, "original").unwrap();
+        
+        let manager = Arc::new(CheckpointManager::new(CheckpointConfig {
+            storage_dir: root.join(".lair"),
+            ..Default::default()
+        }));
+        let earlier = manager.save(None).await.unwrap();
+        let watcher = FileWatcher::start(manager.clone(), WatchConfig {
+            root: root.clone(),
+            debounce: Duration::from_millis(20),
+            ..Default::default()
+        }).unwrap();
+        
+        std::fs::write(&readme, "edited by a shell").unwrap();
+        let marker = root.join("new.txt");
+        std::fs::write(&marker, "new").unwrap();
+        
+        wait_for(|| manager.file_state(&marker).is_some()).await;
+        watcher.stop().await;
+        manager.save(None).await.unwrap();
+        
+        let plan = manager.plan_restore(earlier).await.unwrap();
+        assert_eq!(plan.delete, vec![marker.clone()]);
+        
+        manager.restore(earlier).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&readme).unwrap(), "edited by a shell");
+        assert!(!marker.exists());
+    }
+}